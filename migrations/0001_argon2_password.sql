@@ -0,0 +1,3 @@
+-- Password disimpan dalam format PHC Argon2id ($argon2id$v=19$m=...,t=...,p=...$salt$hash)
+-- Data lama (AES-CTR) di-hash ulang otomatis saat user berhasil login
+ALTER TABLE users ALTER COLUMN password TYPE TEXT;
@@ -0,0 +1,10 @@
+-- Refresh token opaque (disimpan dalam bentuk SHA-256) per baris session di tabel cookies
+ALTER TABLE cookies
+    ADD COLUMN IF NOT EXISTS refresh_token_hash TEXT,
+    ADD COLUMN IF NOT EXISTS token_family TEXT,
+    ADD COLUMN IF NOT EXISTS refresh_expires TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS rotated_refresh_hashes TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE UNIQUE INDEX IF NOT EXISTS cookies_refresh_token_hash_idx ON cookies (refresh_token_hash);
+CREATE INDEX IF NOT EXISTS cookies_token_family_idx ON cookies (token_family);
+CREATE INDEX IF NOT EXISTS cookies_rotated_refresh_hashes_idx ON cookies USING GIN (rotated_refresh_hashes);
@@ -0,0 +1,7 @@
+-- Two-factor authentication (TOTP)
+-- totp_secret disimpan terenkripsi AES-256-GCM (crypto::seal_text), recovery code disimpan dalam bentuk SHA-256
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS totp_secret TEXT,
+    ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS totp_enabled_at TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS totp_recovery_codes TEXT[] NOT NULL DEFAULT '{}';
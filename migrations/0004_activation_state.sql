@@ -0,0 +1,5 @@
+-- count_resend_activation dulu dipakai sebagai flag "sudah aktivasi", sekarang murni jumlah kirim ulang link.
+-- Status aktivasi dibaca dari activate_time.
+ALTER TABLE users ALTER COLUMN count_resend_activation SET DEFAULT 0;
+UPDATE users SET count_resend_activation = 0;
+ALTER TABLE users ALTER COLUMN count_resend_activation SET NOT NULL;
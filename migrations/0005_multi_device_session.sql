@@ -0,0 +1,16 @@
+-- Satu baris cookies per device/browser, bukan lagi satu baris per user
+ALTER TABLE cookies DROP CONSTRAINT IF EXISTS cookies_user_nid_key;
+DROP INDEX IF EXISTS cookies_user_nid_key;
+
+ALTER TABLE cookies
+    ADD COLUMN IF NOT EXISTS session_id TEXT,
+    ADD COLUMN IF NOT EXISTS app_device TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMP;
+
+UPDATE cookies SET session_id = md5(random()::TEXT || clock_timestamp()::TEXT) WHERE session_id IS NULL;
+UPDATE cookies SET created_at = last_update WHERE created_at IS NULL;
+
+ALTER TABLE cookies ALTER COLUMN session_id SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS cookies_session_id_idx ON cookies (session_id);
+CREATE INDEX IF NOT EXISTS cookies_user_nid_idx ON cookies (user_nid);
@@ -0,0 +1,48 @@
+-- Role-based access control
+-- Admin pertama di-assign manual:
+--   INSERT INTO user_roles (user_nid, role_id) SELECT <web_cif_id>, role_id FROM roles WHERE name = 'admin';
+CREATE TABLE IF NOT EXISTS roles (
+    role_id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS permissions (
+    permission_id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role_id INT NOT NULL REFERENCES roles (role_id) ON DELETE CASCADE,
+    permission_id INT NOT NULL REFERENCES permissions (permission_id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission_id)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_nid INT NOT NULL,
+    role_id INT NOT NULL REFERENCES roles (role_id) ON DELETE CASCADE,
+    assigned_by INT,
+    assigned_at TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_nid, role_id)
+);
+
+INSERT INTO permissions (name, description) VALUES
+    ('library.write', 'Create / update notes, skills and portfolio'),
+    ('redis.admin', 'Read, update and clear Redis keys'),
+    ('data.export', 'Read non-public tables through /data'),
+    ('roles.manage', 'Assign and revoke user roles')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles (name, description) VALUES
+    ('admin', 'Full access'),
+    ('editor', 'Manage library content')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.role_id, p.permission_id FROM roles r CROSS JOIN permissions p WHERE r.name = 'admin'
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.role_id, p.permission_id FROM roles r JOIN permissions p ON p.name = 'library.write' WHERE r.name = 'editor'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,7 @@
+-- Ganti email: email baru baru berlaku setelah link konfirmasi (disimpan SHA-256) dibuka
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS pending_email TEXT,
+    ADD COLUMN IF NOT EXISTS email_change_key TEXT,
+    ADD COLUMN IF NOT EXISTS email_change_date TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS users_email_change_key_idx ON users (email_change_key);
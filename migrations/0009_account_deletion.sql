@@ -0,0 +1,10 @@
+-- Hapus akun oleh user sendiri: konfirmasi lewat link (disimpan SHA-256), lalu masa tenggang sebelum dianonimkan.
+-- Baris users / user_kyc / user_request tidak di-DELETE supaya catatan yang wajib disimpan (cif, client_id, tanggal) tetap ada.
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS deletion_key TEXT,
+    ADD COLUMN IF NOT EXISTS deletion_key_date TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS deletion_scheduled_at TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS users_deletion_key_idx ON users (deletion_key);
+CREATE INDEX IF NOT EXISTS users_deletion_scheduled_at_idx ON users (deletion_scheduled_at) WHERE deleted_at IS NULL;
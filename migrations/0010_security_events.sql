@@ -0,0 +1,25 @@
+-- Audit log kejadian keamanan akun (login, logout, aktivasi, reset / ganti password, tautan OAuth)
+CREATE TABLE IF NOT EXISTS security_events (
+    event_id BIGSERIAL PRIMARY KEY,
+    user_nid INT,
+    email TEXT,
+    event_type TEXT NOT NULL,
+    success BOOLEAN NOT NULL DEFAULT true,
+    ip_address TEXT,
+    device_name TEXT,
+    user_agent TEXT,
+    detail TEXT,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS security_events_user_nid_idx ON security_events (user_nid, created_at DESC);
+CREATE INDEX IF NOT EXISTS security_events_type_idx ON security_events (event_type, created_at DESC);
+CREATE INDEX IF NOT EXISTS security_events_created_at_idx ON security_events (created_at DESC);
+
+INSERT INTO permissions (name, description) VALUES
+    ('security_events.read', 'Search the security event audit log of all users')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.role_id, p.permission_id FROM roles r JOIN permissions p ON p.name = 'security_events.read' WHERE r.name = 'admin'
+ON CONFLICT DO NOTHING;
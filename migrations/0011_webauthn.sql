@@ -0,0 +1,14 @@
+-- Passkey (WebAuthn). Public key disimpan dalam format COSE (base64url), sign_count untuk deteksi authenticator yang di-clone
+CREATE TABLE IF NOT EXISTS webauthn_credentials (
+    credential_id TEXT PRIMARY KEY,
+    user_nid INT NOT NULL,
+    name TEXT,
+    public_key TEXT NOT NULL,
+    algorithm INT NOT NULL,
+    sign_count BIGINT NOT NULL DEFAULT 0,
+    transports TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP NOT NULL,
+    last_used_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS webauthn_credentials_user_nid_idx ON webauthn_credentials (user_nid);
@@ -0,0 +1,19 @@
+-- Akun login eksternal (OpenID Connect / OAuth) yang tertaut ke user, satu akun per provider per user
+CREATE TABLE IF NOT EXISTS user_identities (
+    identity_id SERIAL PRIMARY KEY,
+    user_nid INT NOT NULL,
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    created_at TIMESTAMP NOT NULL,
+    last_login_at TIMESTAMP,
+    UNIQUE (provider, subject),
+    UNIQUE (user_nid, provider)
+);
+
+-- Pindahkan tautan Google lama, kolom users.google_id tidak dipakai lagi
+INSERT INTO user_identities (user_nid, provider, subject, email, created_at)
+SELECT web_cif_id, 'google', google_id, email, (now() AT TIME ZONE 'Asia/Jakarta')
+FROM users
+WHERE google_id IS NOT NULL AND google_id <> ''
+ON CONFLICT DO NOTHING;
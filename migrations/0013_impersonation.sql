@@ -0,0 +1,12 @@
+-- Impersonation admin: event yang terjadi saat admin memakai akun user dicatat dengan ID admin di actor_nid
+ALTER TABLE security_events ADD COLUMN IF NOT EXISTS actor_nid INT;
+
+CREATE INDEX IF NOT EXISTS security_events_actor_nid_idx ON security_events (actor_nid, created_at DESC) WHERE actor_nid IS NOT NULL;
+
+INSERT INTO permissions (name, description) VALUES
+    ('users.impersonate', 'Start a read-only session as another user for support')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role_id, permission_id)
+SELECT r.role_id, p.permission_id FROM roles r JOIN permissions p ON p.name = 'users.impersonate' WHERE r.name = 'admin'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,11 @@
+-- Kolom KYC sensitif disimpan sebagai envelope AES-256-GCM (v2.<kid>.<base64url>), lebih panjang dari plaintext.
+-- Data lama dienkripsi bertahap oleh job KycService::seal_pending.
+ALTER TABLE user_kyc
+    ALTER COLUMN idcard_number TYPE TEXT,
+    ALTER COLUMN npwp_number TYPE TEXT,
+    ALTER COLUMN bank_account_number TYPE TEXT,
+    ALTER COLUMN mother_name TYPE TEXT,
+    ALTER COLUMN idcard_file TYPE TEXT,
+    ALTER COLUMN selfie_file TYPE TEXT,
+    ALTER COLUMN signature_file TYPE TEXT,
+    ALTER COLUMN npwp_file TYPE TEXT;
@@ -0,0 +1,204 @@
+use actix_web::{get, post, web, HttpMessage, HttpRequest, HttpResponse, Responder, Scope};
+use validator::Validate;
+
+use crate::{middleware::{jwt_session::Claims, model::{ActionResult, AssignRoleRequest, ImpersonateRequest, RevokeTokensRequest, RoleInfo, SecurityEventInfo, SecurityEventQuery}, permission::RequirePermission}, services::{audit_service::AuditService, auth_service::AuthService, generic_service::GenericService, impersonation_service::ImpersonationService, role_service::RoleService}};
+
+pub fn admin_scope() -> Scope {
+
+    web::scope("/admin")
+        // harus sebelum scope kosong, kalau tidak request ditangkap guard `roles.manage`
+        .service(
+            web::scope("/security-events")
+                .wrap(RequirePermission::new("security_events.read"))
+                .service(search_security_events),
+        )
+        .service(
+            web::scope("/users/{usernid}/impersonate")
+                .wrap(RequirePermission::new("users.impersonate"))
+                .service(impersonate_user),
+        )
+        .service(
+            // scope kosong supaya guard berlaku ke semua route tanpa mengubah tipe return `Scope`
+            web::scope("")
+                .wrap(RequirePermission::new("roles.manage"))
+                .configure(config),
+        )
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_roles)
+        .service(get_user_roles)
+        .service(assign_role)
+        .service(revoke_role)
+        .service(revoke_user_tokens);
+}
+
+#[get("/roles")]
+async fn list_roles() -> impl Responder {
+
+    let result: ActionResult<Vec<RoleInfo>, String> = RoleService::list_roles().await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data.unwrap_or_default()
+        })),
+    }
+}
+
+#[get("/users/{usernid}/roles")]
+async fn get_user_roles(usernid: web::Path<i32>) -> impl Responder {
+
+    let result: ActionResult<Vec<String>, String> = RoleService::user_roles(usernid.into_inner()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data.unwrap_or_default()
+        })),
+    }
+}
+
+#[post("/users/{usernid}/roles")]
+async fn assign_role(req: HttpRequest, usernid: web::Path<i32>, request: web::Json<AssignRoleRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    // Claims admin diisi oleh RequirePermission
+    let admin = req.extensions().get::<Claims>().map(|claims| claims.usernid).unwrap_or_default();
+
+    let result: ActionResult<String, String> = RoleService::assign_role(usernid.into_inner(), request.role.trim(), admin).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::NotFound().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[post("/users/{usernid}/roles/{role}/revoke")]
+async fn revoke_role(path: web::Path<(i32, String)>) -> impl Responder {
+
+    let (usernid, role) = path.into_inner();
+    let result: ActionResult<String, String> = RoleService::revoke_role(usernid, &role).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::NotFound().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[post("/users/{usernid}/tokens/revoke")]
+async fn revoke_user_tokens(usernid: web::Path<i32>, request: Option<web::Json<RevokeTokensRequest>>) -> impl Responder {
+
+    let now = GenericService::get_timestamp();
+
+    let before = match request.and_then(|request| request.into_inner().before) {
+        Some(before) => match chrono::NaiveDateTime::parse_from_str(before.trim(), "%Y-%m-%d %H:%M:%S") {
+            Ok(before) if before <= now => before,
+            Ok(_) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({ "error": "before must not be in the future" }));
+            }
+            Err(_) => {
+                return HttpResponse::BadRequest().json(serde_json::json!({ "error": "Invalid before, expected YYYY-MM-DD HH:MM:SS" }));
+            }
+        },
+        None => now,
+    };
+
+    let result: ActionResult<String, String> = AuthService::revoke_tokens_before(usernid.into_inner(), before).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[get("")]
+async fn search_security_events(query: web::Query<SecurityEventQuery>) -> impl Responder {
+
+    let result: ActionResult<Vec<SecurityEventInfo>, String> = AuditService::search(query.into_inner()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data.unwrap_or_default()
+        })),
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+/// Token bearer read-only atas nama user, tidak mengganti cookie session admin
+#[post("")]
+async fn impersonate_user(req: HttpRequest, usernid: web::Path<i32>, request: web::Json<ImpersonateRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    // API key tidak punya identitas admin untuk audit
+    let Some(admin) = req.extensions().get::<Claims>().cloned() else {
+        return HttpResponse::Forbidden().json(serde_json::json!({ "error": "Impersonation requires an admin session" }));
+    };
+
+    let result: ActionResult<(String, Claims), String> = ImpersonationService::start(&admin, usernid.into_inner(), &request.reason, &req).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        ActionResult { result: true, data: Some((token, user)), message, .. } => HttpResponse::Ok().json(serde_json::json!({
+            "data": {
+                "message": message,
+                "token_type": "Bearer",
+                "access_token": token,
+                "expires_in": user.exp as i64 - chrono::Utc::now().timestamp(),
+                "usernid": user.usernid,
+                "email": user.email,
+                "impersonator": user.impersonator,
+            }
+        })),
+        response if response.message == "User not found" => HttpResponse::NotFound().json(serde_json::json!({ "error": response.message })),
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })),
+    }
+}
@@ -0,0 +1,90 @@
+use actix_web::{get, post, web, HttpMessage, HttpRequest, HttpResponse, Responder, Scope};
+use validator::Validate;
+
+use crate::{middleware::{jwt_session::Claims, model::{ActionResult, ApiKeyInfo, CreateApiKeyRequest}, permission::{Principal, RequirePermission}}, services::api_key_service::ApiKeyService};
+
+pub fn api_key_scope() -> Scope {
+
+    web::scope("/api-keys").service(
+        // scope kosong supaya guard berlaku ke semua route tanpa mengubah tipe return `Scope`
+        web::scope("")
+            .wrap(RequirePermission::new("api_keys.manage"))
+            .configure(config),
+    )
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_api_keys)
+        .service(create_api_key)
+        .service(revoke_api_key);
+}
+
+#[get("")]
+async fn list_api_keys() -> impl Responder {
+
+    let result: ActionResult<Vec<ApiKeyInfo>, String> = ApiKeyService::list().await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data.unwrap_or_default()
+        })),
+    }
+}
+
+#[post("")]
+async fn create_api_key(req: HttpRequest, request: web::Json<CreateApiKeyRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    // Claims / API key pembuat diisi oleh RequirePermission
+    let creator = {
+        let extensions = req.extensions();
+        match (extensions.get::<Claims>(), extensions.get::<ApiKeyInfo>()) {
+            (Some(claims), _) => Principal::User(claims.clone()),
+            (None, Some(info)) => Principal::ApiKey(info.clone()),
+            (None, None) => return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Unauthorized" })),
+        }
+    };
+
+    let result: ActionResult<serde_json::Value, String> = ApiKeyService::create(request.into_inner(), &creator).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "message": response.message,
+            "data": response.data
+        })),
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[post("/{api_key_id}/revoke")]
+async fn revoke_api_key(api_key_id: web::Path<i32>) -> impl Responder {
+
+    let result: ActionResult<String, String> = ApiKeyService::revoke(api_key_id.into_inner()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::NotFound().json(serde_json::json!({ "error": response.message })),
+    }
+}
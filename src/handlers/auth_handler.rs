@@ -1,13 +1,39 @@
 use actix_web::{cookie::{time, Cookie, SameSite}, post, get, web, HttpRequest, HttpResponse, Responder, Scope};
-use oauth2::{AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, DeviceAuthorizationUrl, PkceCodeChallenge, RedirectUrl, TokenResponse, TokenUrl, basic::BasicClient};
 use validator::Validate;
 
-use crate::{AppState, SECRETS, middleware::{
-    jwt_session::{Claims, create_jwt, validate_jwt}, 
-    model::{ActionResult, ChangePasswordRequest, LoginRequest, RegisterRequest, ResetPasswordRequest}}, services::{auth_service::AuthService, generic_service::GenericService
+use crate::{SECRETS, middleware::{
+    auth_extractor::{request_token, verify_token, AuthenticatedUser},
+    csrf::{csrf_cookie, new_csrf_token, CSRF_COOKIE},
+    jwt_session::{Claims, KeyRing, access_token_ttl, create_jwt, refresh_token_ttl}, 
+    oauth_state::OAuthStateStore,
+    model::{ActionResult, AuthModeQuery, ChangeEmailRequest, ChangePasswordRequest, LoginRequest, MfaVerifyRequest, OtpLoginRequest, OtpVerifyRequest, PasskeyInfo, PasskeyLoginOptionsRequest, PasskeyLoginRequest, PasskeyRegisterRequest, RefreshRequest, RegisterRequest, ResendActivationRequest, ResetPasswordRequest, SessionTokens, TotpCodeRequest, UpdatePasswordRequest}}, services::{account_service::AccountService, auth_service::AuthService, generic_service::GenericService, impersonation_service::ImpersonationService, login_guard_service::{LoginBlocked, LoginGuardService}, mfa_service::{MfaService, CHALLENGE_TTL}, oidc_service::OidcService, otp_service::OtpService, role_service::RoleService, webauthn_service::WebauthnService
 }};
 
 const APP_NAME: &str = "snakesystem-api";
+const REFRESH_COOKIE: &str = "snakesystem-api-refresh";
+/// Challenge 2FA dari login provider eksternal, supaya token tidak ikut di URL redirect (history, `Referer`, log proxy)
+const MFA_COOKIE: &str = "snakesystem-api-mfa";
+
+fn session_cookie(name: &'static str, value: String, max_age: chrono::Duration) -> Cookie<'static> {
+    let mut cookie = Cookie::build(name, value)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::None) // ❗ WAJIB None agar cookie cross-site
+        .secure(true)
+        .expires(time::OffsetDateTime::now_utc() + time::Duration::seconds(max_age.num_seconds()))
+        .finish();
+
+    // Refresh token cuma dikirim ke endpoint auth, challenge 2FA cuma ke endpoint 2FA
+    if name == REFRESH_COOKIE {
+        cookie.set_path("/api/v1/auth");
+    }
+    if name == MFA_COOKIE {
+        cookie.set_path("/api/v1/auth/2fa");
+        cookie.set_same_site(SameSite::Lax);
+    }
+
+    cookie
+}
 
 pub fn auth_scope() -> Scope {
     
@@ -19,20 +45,160 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(login)
         .service(check_session)
         .service(register)
+        .service(resend_activation) // harus sebelum /activation/{activation_url}
         .service(activation)
         .service(reset_password)
         .service(change_password)
+        .service(refresh)
         .service(logout)
         .service(google_login)
-        .service(google_callback);
+        .service(google_callback)
+        .service(oidc_providers)
+        .service(oidc_login)
+        .service(oidc_callback)
+        .service(mfa_setup)
+        .service(mfa_confirm)
+        .service(mfa_disable)
+        .service(mfa_verify)
+        .service(otp_request)
+        .service(otp_verify)
+        .service(list_sessions)
+        .service(revoke_other_sessions)
+        .service(revoke_session)
+        .service(update_password)
+        .service(change_email)
+        .service(confirm_email_change)
+        .service(get_csrf_token)
+        .service(passkey_register_options)
+        .service(passkey_register)
+        .service(passkey_login_options)
+        .service(passkey_login)
+        .service(list_passkeys)
+        .service(delete_passkey)
+        .service(stop_impersonation);
+}
+
+/// Response login sukses: default pakai cookie, `?mode=token` kirim token di body untuk client non-browser
+fn session_response(tokens: SessionTokens, message: String, token_mode: bool) -> HttpResponse {
+    if token_mode {
+        return HttpResponse::Ok().json(serde_json::json!({
+            "data": {
+                "message": message,
+                "token_type": "Bearer",
+                "access_token": tokens.access_token,
+                "expires_in": access_token_ttl().num_seconds(),
+                "refresh_token": tokens.refresh_token,
+                "refresh_expires_in": tokens.refresh_ttl.num_seconds(),
+            }
+        }));
+    }
+
+    let csrf_token = new_csrf_token();
+
+    HttpResponse::Ok()
+        .cookie(session_cookie(APP_NAME, tokens.access_token, access_token_ttl()))
+        .cookie(session_cookie(REFRESH_COOKIE, tokens.refresh_token, tokens.refresh_ttl))
+        .cookie(csrf_cookie(csrf_token.clone(), tokens.refresh_ttl))
+        .json(serde_json::json!({ "data": message, "csrf_token": csrf_token }))
+}
+
+/// Token CSRF yang sedang berlaku (dari cookie), dibuat baru kalau belum ada
+fn current_csrf_token(req: &HttpRequest) -> String {
+    req.cookie(CSRF_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+        .unwrap_or_else(new_csrf_token)
+}
+
+/// ✅ Buat JWT + baris `cookies` + refresh token untuk user yang sudah lolos autentikasi.
+/// Dipakai semua jalur login (password, Google, dll) supaya session yang terbentuk sama persis.
+async fn start_session(mut user: Claims) -> Result<(SessionTokens, String), HttpResponse> {
+    if let Err(e) = RoleService::attach_access(&mut user).await {
+        return Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": e })));
+    }
+
+    let (token, user) = match create_jwt(user) {
+        Ok(minted) => minted,
+        Err(err) => {
+            println!("❌ Failed to create JWT: {}", err);
+            return Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": "Failed to create JWT" })));
+        }
+    };
+
+    // ✅ Simpan token dalam tabel cookies
+    let result = AuthService::check_session(user.clone(), token.clone(), "".to_string(), false, false, false, APP_NAME).await;
+
+    if result.error.is_some() {
+        return Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": result.error })));
+    }
+
+    if !result.result {
+        return Err(HttpResponse::BadRequest().json(serde_json::json!({ "error": result.message })));
+    }
+
+    let refresh_result = AuthService::issue_refresh_token(user.usernid, &token, None).await;
+
+    let refresh_token = match refresh_result.data {
+        Some(refresh_token) => refresh_token,
+        None => {
+            return Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": refresh_result.error.unwrap_or(refresh_result.message) })));
+        }
+    };
+
+    let refresh_ttl = AuthService::refresh_ttl_for(GenericService::get_timestamp());
+
+    Ok((SessionTokens { claims: user, access_token: token, refresh_token, refresh_ttl }, result.message))
+}
+
+/// Kalau user mengaktifkan 2FA, login berhenti di challenge dan cookie belum diberikan
+async fn second_factor_challenge(user: &Claims) -> Result<Option<String>, HttpResponse> {
+    match MfaService::is_enabled(user.usernid).await {
+        Ok(false) => Ok(None),
+        Ok(true) => match MfaService::create_challenge(user) {
+            Ok(token) => Ok(Some(token)),
+            Err(e) => {
+                println!("❌ MFA Challenge Error: {}", e);
+                Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": "Failed to create two-factor challenge" })))
+            }
+        },
+        Err(e) => Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": e }))),
+    }
+}
+
+/// Response `423` / `429` untuk percobaan login yang ditolak [`LoginGuardService`]
+fn login_blocked_response(blocked: LoginBlocked) -> HttpResponse {
+    match blocked {
+        LoginBlocked::Locked { retry_after } => HttpResponse::Locked()
+            .insert_header(("Retry-After", retry_after.to_string()))
+            .json(serde_json::json!({
+                "error": "Account temporarily locked due to too many failed login attempts",
+                "retry_after": retry_after
+            })),
+        LoginBlocked::Throttled { retry_after } => HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after.to_string()))
+            .json(serde_json::json!({
+                "error": "Too many login attempts, please try again later",
+                "retry_after": retry_after
+            })),
+    }
 }
 
 #[post("/login")]
-async fn login(req: HttpRequest, request: web::Json<LoginRequest>) -> impl Responder {
-    
-    let mut result: ActionResult<Claims, _> = AuthService::login(request.into_inner(), &req, APP_NAME).await;
+async fn login(req: HttpRequest, mode: web::Query<AuthModeQuery>, request: web::Json<LoginRequest>) -> impl Responder {
+
+    let email = request.email.clone().unwrap_or_default();
+    let ip_address = GenericService::get_ip_address(&req);
+
+    // Tolak dulu kalau akun terkunci / masih dalam jeda brute-force, sekaligus reservasi percobaan ini
+    let attempt = match LoginGuardService::reserve(&email, &ip_address) {
+        Ok(Ok(attempt)) => attempt,
+        Ok(Err(blocked)) => return login_blocked_response(blocked),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "error": format!("Redis error: {}", e) }));
+        }
+    };
 
-    // let token_cookie = req.cookie(APP_NAME).map(|c| c.value().to_string()).unwrap_or_default();
+    let result: ActionResult<Claims, _> = AuthService::login(request.into_inner(), &req, APP_NAME).await;
 
     match result {
         response if response.error.is_some() => {
@@ -40,94 +206,47 @@ async fn login(req: HttpRequest, request: web::Json<LoginRequest>) -> impl Respo
                 "error": response.error
             }))
         }, // Jika error, HTTP 500
-        response if response.result => {
-            if let Some(user) = &response.data {
-                // ✅ Buat token JWT
-                match create_jwt(user.clone()) {
-                    Ok(token) => {
-                        // ✅ Simpan token dalam cookie
-                        result = AuthService::check_session(user.clone(), token.clone(), "".to_string(), false, false, false, APP_NAME).await;
-
-                        // ✅ Jika berhasil, kembalikan JSON response
-                        if result.error.is_some() {
-                            return HttpResponse::InternalServerError().json(serde_json::json!({ "error": result.error }));
-                        }
-
-                        if result.result {
-                            let cookie = Cookie::build(APP_NAME, token)
-                            .path("/")
-                            .http_only(true)
-                            .same_site(SameSite::None) // ❗ WAJIB None agar cookie cross-site
-                            .secure(true)     
-                            .expires(time::OffsetDateTime::now_utc() + time::Duration::days(2)) // Set expired 2 hari
-                            .finish();
-
-                            return HttpResponse::Ok()
-                                .cookie(cookie)
-                                .json(serde_json::json!({ "data": result.message }));
-                        }
-                            
-                        if !result.result {
-                            return HttpResponse::BadRequest().json(serde_json::json!({ "error": result.message }));
-                        }
-                    }
-                    Err(err) => {
-                        println!("❌ Failed to create JWT: {}", err);
-                        return HttpResponse::InternalServerError().json(serde_json::json!({ "error": "Failed to create JWT" }));
+        ActionResult { result: true, data: Some(user), .. } => {
+            match second_factor_challenge(&user).await {
+                Ok(Some(mfa_token)) => {
+                    // Counter gagal baru direset setelah 2FA lolos, cukup lepas jeda supaya kode bisa langsung dikirim
+                    if let Err(e) = LoginGuardService::release_delay(&email) {
+                        println!("❌ Login Guard Error: {}", e);
                     }
+
+                    return HttpResponse::Ok().json(serde_json::json!({
+                        "data": { "mfa_required": true, "mfa_token": mfa_token }
+                    }));
                 }
+                Ok(None) => {}
+                Err(response) => return response,
+            }
+
+            if let Err(e) = LoginGuardService::record_success(&email, &ip_address) {
+                println!("❌ Login Guard Error: {}", e);
             }
 
-            HttpResponse::BadRequest().json(response) // Jika tidak ada user, return 400
+            match start_session(user).await {
+                Ok((tokens, message)) => session_response(tokens, message, mode.token_mode()),
+                Err(response) => response,
+            }
+        },
+        response => {
+            if let Err(e) = LoginGuardService::record_failure(&email, &ip_address, attempt).await {
+                println!("❌ Login Guard Error: {}", e);
+            }
+
+            HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })) // Jika gagal login, HTTP 400
         },
-        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal login, HTTP 400
     }
 }
 
 #[get("/session")]
-async fn check_session(req: HttpRequest) -> impl Responder {
-
-    let mut result: ActionResult<Claims, _> = ActionResult::default();
-
-    // Ambil cookie "token"
-    let token_cookie = req.cookie(APP_NAME);
+async fn check_session(user: AuthenticatedUser) -> impl Responder {
 
-    // Cek apakah token ada di cookie
-    let token = match token_cookie {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            result.error = Some("Token not found".to_string());
-            return HttpResponse::Unauthorized().json(result);
-        }
-    };
-
-    // Validate token
-    match validate_jwt(&token) {
-        Ok(claims) => {
-            result = AuthService::check_session(claims.clone(), token.clone(), token.clone(), false, false, true, APP_NAME).await;
-
-            match result {
-                response if response.error.is_some() => {
-                    HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": response.error
-                    }))
-                },
-                response if response.result => {
-                    
-                    HttpResponse::Ok().json({
-                        serde_json::json!({
-                            "data": Some(claims.clone())
-                        })
-                    })
-                },
-                response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal login, HTTP 400
-            }
-        },
-        Err(err) => {
-            result.error = Some(err.to_string());
-            HttpResponse::Unauthorized().json(serde_json::json!({ "error": result.error }))
-        },
-    }
+    HttpResponse::Ok().json(serde_json::json!({
+        "data": user.claims
+    }))
 }
 
 #[post("/register")]
@@ -158,8 +277,37 @@ async fn register(req: HttpRequest, mut request: web::Json<RegisterRequest>) ->
     }
 }
 
+#[post("/activation/resend")]
+async fn resend_activation(request: web::Json<ResendActivationRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let result: ActionResult<i64, String> = AuthService::resend_activation(request.into_inner()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })), // Jika berhasil, HTTP 200
+        response => {
+            let retry_after = response.data.unwrap_or(0);
+            HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(serde_json::json!({ "error": response.message, "retry_after": retry_after }))
+        }, // Masih cooldown, HTTP 429
+    }
+}
+
 #[post("/activation/{activation_url}")]
-async fn activation(activation_url: web::Path<String>) -> impl Responder {
+async fn activation(req: HttpRequest, activation_url: web::Path<String>) -> impl Responder {
 
     if activation_url.is_empty() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -167,7 +315,7 @@ async fn activation(activation_url: web::Path<String>) -> impl Responder {
         }));
     }
 
-    let result: ActionResult<String, String> = AuthService::activation(activation_url.to_string()).await;
+    let result: ActionResult<String, String> = AuthService::activation(activation_url.to_string(), &req).await;
 
     match result {
         response if response.error.is_some() => {
@@ -185,7 +333,7 @@ async fn activation(activation_url: web::Path<String>) -> impl Responder {
 }
 
 #[post("/reset-password")]
-async fn reset_password(request: web::Json<ResetPasswordRequest>) -> impl Responder {
+async fn reset_password(req: HttpRequest, request: web::Json<ResetPasswordRequest>) -> impl Responder {
 
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -193,7 +341,7 @@ async fn reset_password(request: web::Json<ResetPasswordRequest>) -> impl Respon
         }));
     }
 
-    let result: ActionResult<String, String> = AuthService::reset_password(request.into_inner()).await;
+    let result: ActionResult<String, String> = AuthService::reset_password(request.into_inner(), &req).await;
 
     match result {
         response if response.error.is_some() => {
@@ -209,7 +357,7 @@ async fn reset_password(request: web::Json<ResetPasswordRequest>) -> impl Respon
 }
 
 #[post("/change-password")]
-async fn change_password(request: web::Json<ChangePasswordRequest>) -> impl Responder {
+async fn change_password(req: HttpRequest, request: web::Json<ChangePasswordRequest>) -> impl Responder {
 
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -217,7 +365,7 @@ async fn change_password(request: web::Json<ChangePasswordRequest>) -> impl Resp
         }));
     }
 
-    let result: ActionResult<String, String> = AuthService::change_password(request.into_inner()).await;
+    let result: ActionResult<String, String> = AuthService::change_password(request.into_inner(), &req).await;
 
     match result {
         response if response.error.is_some() => {
@@ -232,28 +380,66 @@ async fn change_password(request: web::Json<ChangePasswordRequest>) -> impl Resp
     }
 }
 
+#[post("/refresh")]
+async fn refresh(req: HttpRequest, request: Option<web::Json<RefreshRequest>>) -> impl Responder {
+
+    // Client non-browser kirim refresh token di body, browser lewat cookie
+    let body_token = request.and_then(|body| body.into_inner().refresh_token).filter(|token| !token.is_empty());
+    let token_mode = body_token.is_some();
+
+    let refresh_token = match body_token.or_else(|| req.cookie(REFRESH_COOKIE).map(|cookie| cookie.value().to_string()).filter(|token| !token.is_empty())) {
+        Some(token) => token,
+        None => {
+            return HttpResponse::Unauthorized().json(serde_json::json!({ "error": "Refresh token not found" }));
+        }
+    };
+
+    let result: ActionResult<SessionTokens, String> = AuthService::refresh_session(refresh_token, &req, APP_NAME).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        ActionResult { result: true, data: Some(tokens), .. } if token_mode => {
+            session_response(tokens, "Session refreshed".to_string(), true)
+        },
+        ActionResult { result: true, data: Some(tokens), .. } => {
+            let csrf_token = current_csrf_token(&req);
+
+            HttpResponse::Ok()
+                .cookie(session_cookie(APP_NAME, tokens.access_token, access_token_ttl()))
+                // Cookie di-issue ulang dengan sisa umur session, bukan umur refresh token penuh
+                .cookie(session_cookie(REFRESH_COOKIE, tokens.refresh_token, tokens.refresh_ttl))
+                .cookie(csrf_cookie(csrf_token.clone(), tokens.refresh_ttl))
+                .json(serde_json::json!({ "data": tokens.claims, "csrf_token": csrf_token }))
+        },
+        response => HttpResponse::Unauthorized()
+            .cookie(session_cookie(APP_NAME, String::new(), chrono::Duration::zero()))
+            .cookie(session_cookie(REFRESH_COOKIE, String::new(), chrono::Duration::zero()))
+            .cookie(csrf_cookie(String::new(), chrono::Duration::zero()))
+            .json(serde_json::json!({ "error": response.message })), // Refresh token tidak valid, HTTP 401
+    }
+}
+
 #[post("/logout")]
 async fn logout(req: HttpRequest) -> impl Responder {
 
     let mut result: ActionResult<Claims, _> = ActionResult::default();
 
-    // Ambil cookie "token"
-    let token_cookie = req.cookie(APP_NAME);
-
-    println!("token_cookie: {:#?}", token_cookie);
-
-    // Cek apakah token ada di cookie
-    let token = match token_cookie {
-        Some(cookie) => cookie.value().to_string(),
+    // Ambil token dari header Authorization / cookie
+    let token = match request_token(&req) {
+        Some(token) => token,
         None => {
             result.error = Some("Token not found".to_string());
             return HttpResponse::Unauthorized().json(result);
         }
     };
 
-    match validate_jwt(&token) {
+    match verify_token(&token) {
         Ok(claims) => {
-            result = AuthService::check_session(claims.clone(), token.clone(), token.clone(), true, false, false, APP_NAME).await;
+            result = AuthService::logout(claims, token, &req, APP_NAME).await;
 
             match result {
                 response if response.error.is_some() => {
@@ -262,87 +448,90 @@ async fn logout(req: HttpRequest) -> impl Responder {
                     }))
                 },
                 response if response.result => {
-                    
-                    let cookie = Cookie::build(APP_NAME, "")
-                    .path("/")
-                    .http_only(true)
-                    .same_site(SameSite::None) // ❗ WAJIB None agar cookie cross-site
-                    .secure(true)     
-                    .expires(time::OffsetDateTime::now_utc() + time::Duration::days(2)) // Set expired 2 hari
-                    .finish();
 
                     return HttpResponse::Ok()
-                        .cookie(cookie)
+                        .cookie(session_cookie(APP_NAME, String::new(), chrono::Duration::zero()))
+                        .cookie(session_cookie(REFRESH_COOKIE, String::new(), chrono::Duration::zero()))
+                        .cookie(csrf_cookie(String::new(), chrono::Duration::zero()))
                         .json(serde_json::json!({ "data": response.message }));
                     
                 },
                 response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal login, HTTP 400
             }
         },
-        Err(err) => {
-            result.error = Some(err.to_string());
-            HttpResponse::Unauthorized().json(serde_json::json!({ "error": result.error }))
-        },
+        Err(err) => err.to_response(),
     }
     
 }
 
-#[get("/google/login")]
-async fn google_login(data: web::Data<AppState>) -> impl Responder {
-
+/// Redirect balik ke frontend dengan pesan error di query string
+fn front_redirect_error(message: &str) -> HttpResponse {
     let secrets = SECRETS.get().expect("SECRETS not initialized");
-    let client_id = secrets.get("GOOGLE_ID").expect("secret was not found");
-    let client_secret = secrets.get("GOOGLE_SECRET").expect("secret was not found");
-    let domain = secrets.get("DOMAIN").expect("secret was not found");
-
-    let redirect_url = format!("{}/api/v1/auth/google/callback", domain);
-
-    let client = BasicClient::new(ClientId::new(client_id.to_string()),)
-    .set_client_secret(ClientSecret::new(client_secret.to_string()))
-    .set_auth_uri(AuthUrl::new("https://accounts.google.com/o/oauth2/auth".to_string()).unwrap())
-    .set_token_uri(TokenUrl::new("https://oauth2.googleapis.com/token".to_string()).unwrap())
-    // Set the URL the user will be redirected to after the authorization process.
-    .set_redirect_uri(RedirectUrl::new(redirect_url).unwrap());
-
-    // PKCE
-    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
-
-    // URL + CSRF Token
-    let (auth_url, csrf_token) = client
-        .authorize_url(CsrfToken::new_random)
-        // .add_scope(oauth2::Scope::new("read".to_string()))
-        .add_scope(oauth2::Scope::new("openid".to_string()))
-        .add_scope(oauth2::Scope::new("email".to_string()))
-        .add_scope(oauth2::Scope::new("profile".to_string()))
-        .set_pkce_challenge(pkce_challenge)
-        .url();
-
-    // Simpan csrf + pkce ke AppState (Arc<Mutex<...>>)
-    {
-        let mut store = data.oauth_store.lock().unwrap();
-        store.insert(csrf_token.secret().to_string(), (csrf_token.clone(), pkce_verifier));
-    }
+    let front_url = secrets.get("FRONT_URL").expect("secret was not found");
 
     HttpResponse::Found()
-        .append_header(("Location", auth_url.to_string()))
+        .append_header(("Location", format!("{}/login?error={}", front_url, urlencoding::encode(message))))
         .finish()
 }
 
+#[get("/oidc/providers")]
+async fn oidc_providers() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "data": OidcService::provider_infos()
+    }))
+}
+
+#[get("/oidc/{provider}/login")]
+async fn oidc_login(req: HttpRequest, provider: web::Path<String>) -> impl Responder {
+    oidc_redirect(&req, &provider).await
+}
+
+#[get("/oidc/{provider}/callback")]
+async fn oidc_callback(req: HttpRequest, provider: web::Path<String>, query: web::Query<std::collections::HashMap<String,String> >) -> impl Responder {
+    oidc_finish(&req, &provider, &query).await
+}
+
+/// Alias lama, redirect URI Google yang terdaftar masih `/auth/google/callback`
+#[get("/google/login")]
+async fn google_login(req: HttpRequest) -> impl Responder {
+    oidc_redirect(&req, "google").await
+}
+
 #[get("/google/callback")]
-async fn google_callback(query: web::Query<std::collections::HashMap<String,String> >, data: web::Data<AppState>) -> impl Responder {
+async fn google_callback(req: HttpRequest, query: web::Query<std::collections::HashMap<String,String> >) -> impl Responder {
+    oidc_finish(&req, "google", &query).await
+}
+
+/// Redirect ke halaman consent provider (PKCE + CSRF state + nonce)
+async fn oidc_redirect(req: &HttpRequest, name: &str) -> HttpResponse {
+    let Some(provider) = OidcService::provider(name) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "Unknown sign-in provider" }));
+    };
+
+    match OidcService::authorization_url(&provider, GenericService::get_ip_address(req)).await {
+        Ok(Some(auth_url)) => HttpResponse::Found()
+            .append_header(("Location", auth_url))
+            .finish(),
+        Ok(None) => HttpResponse::TooManyRequests().json(serde_json::json!({ "error": "Too many pending sign-in attempts, please try again later" })),
+        Err(e) => {
+            println!("❌ OIDC Login Error: {}", e);
+            HttpResponse::BadGateway().json(serde_json::json!({ "error": "Sign-in provider is unavailable" }))
+        }
+    }
+}
+
+async fn oidc_finish(req: &HttpRequest, name: &str, query: &std::collections::HashMap<String, String>) -> HttpResponse {
     let secrets = SECRETS.get().expect("SECRETS not initialized");
-    let client_id = secrets.get("GOOGLE_ID").expect("secret was not found");
-    let client_secret = secrets.get("GOOGLE_SECRET").expect("secret was not found");
-    let domain = secrets.get("DOMAIN").expect("secret was not found");
+    let front_url = secrets.get("FRONT_URL").expect("secret was not found");
 
-    let redirect_url = format!("{}/api/v1/auth/google/callback", domain);
+    let Some(provider) = OidcService::provider(name) else {
+        return front_redirect_error("Unknown sign-in provider");
+    };
 
-    let client = BasicClient::new(ClientId::new(client_id.to_string()))
-        .set_client_secret(ClientSecret::new(client_secret.to_string()))
-        .set_device_authorization_url(DeviceAuthorizationUrl::new("https://oauth2.googleapis.com/device/code".into()).unwrap())
-        .set_auth_uri(AuthUrl::new("https://accounts.google.com/o/oauth2/v2/auth".into()).unwrap())
-        .set_token_uri(TokenUrl::new("https://oauth2.googleapis.com/token".into()).unwrap())
-        .set_redirect_uri(RedirectUrl::new(redirect_url).unwrap());
+    // user menolak consent / provider mengembalikan error
+    if let Some(err) = query.get("error") {
+        return front_redirect_error(err);
+    }
 
     // ambil query params (cek ada atau nggak)
     let code = match query.get("code") {
@@ -354,39 +543,567 @@ async fn google_callback(query: web::Query<std::collections::HashMap<String,Stri
         None => return HttpResponse::BadRequest().body("Missing state"),
     };
 
-    // ambil & hapus pair dari store (one-time use)
-    let pair_opt = {
-        let mut store = data.oauth_store.lock().unwrap();
-        store.remove(&state)
+    // ambil & hapus state dari Redis (one-time use), state yang tidak dikenal = CSRF tidak valid
+    let pending = match OAuthStateStore::take(&state) {
+        Ok(Some(pending)) if pending.provider == provider.name => pending,
+        Ok(_) => return HttpResponse::BadRequest().body("Invalid or expired state"),
+        Err(e) => {
+            println!("❌ OAuth State Error: {}", e);
+            return front_redirect_error("Internal server error");
+        }
     };
 
-    let (csrf_token, pkce_verifier) = match pair_opt {
-        Some(pair) => pair,
-        None => return HttpResponse::BadRequest().body("Invalid or expired state"),
+    let identity = match OidcService::fetch_identity(&provider, &code, &pending).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            println!("❌ OIDC Callback Error ({}): {}", provider.name, e);
+            return front_redirect_error(&format!("Failed to sign in with {}", provider.display_name));
+        }
     };
 
-    // verifikasi CSRF
-    if csrf_token.secret() != &state {
-        return HttpResponse::Unauthorized().body("Invalid CSRF token");
+    let result: ActionResult<Claims, String> = AuthService::external_login(identity, req, APP_NAME).await;
+
+    match result {
+        response if response.error.is_some() => {
+            println!("❌ OIDC Login Error: {:?}", response.error);
+            front_redirect_error("Internal server error")
+        },
+        ActionResult { result: true, data: Some(user), .. } => {
+            match second_factor_challenge(&user).await {
+                Ok(Some(mfa_token)) => {
+                    return HttpResponse::Found()
+                        .cookie(session_cookie(MFA_COOKIE, mfa_token, chrono::Duration::seconds(CHALLENGE_TTL as i64)))
+                        .append_header(("Location", format!("{}/login/2fa", front_url)))
+                        .finish();
+                }
+                Ok(None) => {}
+                Err(_) => return front_redirect_error("Failed to create two-factor challenge"),
+            }
+
+            match start_session(user).await {
+                Ok((tokens, _)) => HttpResponse::Found()
+                    .cookie(session_cookie(APP_NAME, tokens.access_token, access_token_ttl()))
+                    .cookie(session_cookie(REFRESH_COOKIE, tokens.refresh_token, tokens.refresh_ttl))
+                    // Frontend beda domain mengambil token lewat `GET /auth/csrf` setelah redirect
+                    .cookie(csrf_cookie(new_csrf_token(), tokens.refresh_ttl))
+                    .append_header(("Location", front_url))
+                    .finish(),
+                Err(_) => front_redirect_error("Failed to start session"),
+            }
+        },
+        response => front_redirect_error(&response.message),
+    }
+}
+
+#[post("/2fa/setup")]
+async fn mfa_setup(user: AuthenticatedUser) -> impl Responder {
+
+    let session = user.claims;
+
+    let result: ActionResult<serde_json::Value, String> = MfaService::setup(session).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data,
+            "message": response.message
+        })), // Jika berhasil, HTTP 200
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal, HTTP 400
+    }
+}
+
+#[post("/2fa/confirm")]
+async fn mfa_confirm(user: AuthenticatedUser, request: web::Json<TotpCodeRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let session = user.claims;
+
+    let result: ActionResult<Vec<String>, String> = MfaService::confirm(session, &request.code).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": { "recovery_codes": response.data },
+            "message": response.message
+        })), // Jika berhasil, HTTP 200
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal, HTTP 400
+    }
+}
+
+#[post("/2fa/disable")]
+async fn mfa_disable(user: AuthenticatedUser, request: web::Json<TotpCodeRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let session = user.claims;
+
+    let result: ActionResult<String, String> = MfaService::disable(session, &request.code).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })), // Jika berhasil, HTTP 200
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal, HTTP 400
+    }
+}
+
+/// Langkah kedua login: tukar `mfa_token` (body, atau cookie dari login provider eksternal) + kode TOTP / recovery code dengan cookie session.
+/// Kode salah dihitung [`LoginGuardService`] per email, jadi jeda dan kunci akun juga berlaku di langkah ini.
+#[post("/2fa/verify")]
+async fn mfa_verify(req: HttpRequest, mode: web::Query<AuthModeQuery>, request: web::Json<MfaVerifyRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let cookie_token = req.cookie(MFA_COOKIE).map(|cookie| cookie.value().to_string());
+    let Some(mfa_token) = Some(request.mfa_token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .or(cookie_token) else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "mfa_token is required" }));
+    };
+
+    let user = match MfaService::load_challenge(&mfa_token) {
+        ActionResult { result: true, data: Some(user), .. } => user,
+        response if response.error.is_some() => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "error": response.error }));
+        }
+        response => return HttpResponse::Unauthorized().json(serde_json::json!({ "error": response.message })),
+    };
+
+    let email = user.email.clone();
+    let ip_address = GenericService::get_ip_address(&req);
+
+    let attempt = match LoginGuardService::reserve(&email, &ip_address) {
+        Ok(Ok(attempt)) => attempt,
+        Ok(Err(blocked)) => return login_blocked_response(blocked),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "error": format!("Redis error: {}", e) }));
+        }
+    };
+
+    let result: ActionResult<Claims, String> = MfaService::verify_challenge(&mfa_token, user, &request.code, &req).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        ActionResult { result: true, data: Some(user), .. } => {
+            if let Err(e) = LoginGuardService::record_success(&email, &ip_address) {
+                println!("❌ Login Guard Error: {}", e);
+            }
+
+            match start_session(user).await {
+                Ok((tokens, message)) => {
+                    // Challenge sudah terpakai, cookie-nya ikut dihapus
+                    let mut response = session_response(tokens, message, mode.token_mode());
+                    if let Err(e) = response.add_cookie(&session_cookie(MFA_COOKIE, String::new(), chrono::Duration::zero())) {
+                        println!("❌ Cookie Error: {}", e);
+                    }
+                    response
+                },
+                Err(response) => response,
+            }
+        },
+        response => {
+            if let Err(e) = LoginGuardService::record_failure(&email, &ip_address, attempt).await {
+                println!("❌ Login Guard Error: {}", e);
+            }
+
+            HttpResponse::Unauthorized().json(serde_json::json!({ "error": response.message })) // Kode salah, HTTP 401
+        },
     }
+}
 
-    // exchange code -> token (async)
-    let http_client = oauth2::reqwest::ClientBuilder::new()
-        .redirect(oauth2::reqwest::redirect::Policy::none())
-        .build()
-        .unwrap();
+#[post("/otp/request")]
+async fn otp_request(request: web::Json<OtpLoginRequest>) -> impl Responder {
 
-    let token_result = client
-        .exchange_code(AuthorizationCode::new(code))
-        .set_pkce_verifier(pkce_verifier)
-        .request_async(&http_client)
-        .await;
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
 
-    match token_result {
-        Ok(token) => {
-            let access_token = token.access_token().secret();
-            HttpResponse::Ok().body(format!("Access token: {}", access_token))
+    let result: ActionResult<String, String> = OtpService::request(&request.email.clone().unwrap_or_default()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+    }
+}
+
+#[post("/otp/verify")]
+async fn otp_verify(req: HttpRequest, mode: web::Query<AuthModeQuery>, request: web::Json<OtpVerifyRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let email = request.email.clone().unwrap_or_default();
+    let ip_address = GenericService::get_ip_address(&req);
+
+    // Guard yang sama dengan login password: kunci akun, jeda progresif, dan batas per IP
+    let attempt = match LoginGuardService::reserve(&email, &ip_address) {
+        Ok(Ok(attempt)) => attempt,
+        Ok(Err(blocked)) => return login_blocked_response(blocked),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "error": format!("Redis error: {}", e) }));
         }
-        Err(err) => HttpResponse::InternalServerError().body(format!("Error: {:?}", err)),
+    };
+
+    let result: ActionResult<Claims, String> = OtpService::verify(&email, &request.code, &req, APP_NAME).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        ActionResult { result: true, data: Some(user), .. } => {
+            match second_factor_challenge(&user).await {
+                Ok(Some(mfa_token)) => {
+                    if let Err(e) = LoginGuardService::release_delay(&email) {
+                        println!("❌ Login Guard Error: {}", e);
+                    }
+
+                    return HttpResponse::Ok().json(serde_json::json!({
+                        "data": { "mfa_required": true, "mfa_token": mfa_token }
+                    }));
+                }
+                Ok(None) => {}
+                Err(response) => return response,
+            }
+
+            if let Err(e) = LoginGuardService::record_success(&email, &ip_address) {
+                println!("❌ Login Guard Error: {}", e);
+            }
+
+            match start_session(user).await {
+                Ok((tokens, message)) => session_response(tokens, message, mode.token_mode()),
+                Err(response) => response,
+            }
+        },
+        response => {
+            if let Err(e) = LoginGuardService::record_failure(&email, &ip_address, attempt).await {
+                println!("❌ Login Guard Error: {}", e);
+            }
+
+            HttpResponse::Unauthorized().json(serde_json::json!({ "error": response.message })) // Kode salah, HTTP 401
+        },
+    }
+}
+
+#[get("/sessions")]
+async fn list_sessions(user: AuthenticatedUser) -> impl Responder {
+
+    let AuthenticatedUser { claims: session, token } = user;
+
+    let result = AuthService::list_sessions(session.usernid, &token).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data.unwrap_or_default()
+        })),
+    }
+}
+
+#[post("/sessions/revoke-others")]
+async fn revoke_other_sessions(user: AuthenticatedUser) -> impl Responder {
+
+    let AuthenticatedUser { claims: session, token } = user;
+
+    let result: ActionResult<String, String> = AuthService::revoke_other_sessions(session.usernid, &token).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+    }
+}
+
+#[post("/sessions/{session_id}/revoke")]
+async fn revoke_session(user: AuthenticatedUser, session_id: web::Path<String>) -> impl Responder {
+
+    let session = user.claims;
+
+    let result: ActionResult<String, String> = AuthService::revoke_session(session.usernid, &session_id).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::NotFound().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[post("/password")]
+async fn update_password(req: HttpRequest, user: AuthenticatedUser, request: web::Json<UpdatePasswordRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let AuthenticatedUser { claims: session, token } = user;
+
+    let result: ActionResult<String, String> = AccountService::change_password(session, &token, request.into_inner(), &req).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })), // Jika berhasil, HTTP 200
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal, HTTP 400
+    }
+}
+
+#[post("/email")]
+async fn change_email(user: AuthenticatedUser, request: web::Json<ChangeEmailRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
     }
-}
\ No newline at end of file
+
+    let result: ActionResult<String, String> = AccountService::request_email_change(user.claims, request.into_inner()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })), // Jika berhasil, HTTP 200
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal, HTTP 400
+    }
+}
+
+#[post("/email/confirm/{change_key}")]
+async fn confirm_email_change(change_key: web::Path<String>) -> impl Responder {
+
+    let result: ActionResult<String, String> = AccountService::confirm_email_change(&change_key).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok()
+            .cookie(session_cookie(APP_NAME, String::new(), chrono::Duration::zero()))
+            .cookie(session_cookie(REFRESH_COOKIE, String::new(), chrono::Duration::zero()))
+            .cookie(csrf_cookie(String::new(), chrono::Duration::zero()))
+            .json(serde_json::json!({
+                "data": response.message
+            })), // Jika berhasil, HTTP 200
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal, HTTP 400
+    }
+}
+
+/// Token CSRF untuk frontend beda domain yang tidak bisa membaca cookie `snakesystem-csrf` (mis. setelah reload halaman)
+#[get("/csrf")]
+async fn get_csrf_token(req: HttpRequest) -> impl Responder {
+
+    let csrf_token = current_csrf_token(&req);
+
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .cookie(csrf_cookie(csrf_token.clone(), refresh_token_ttl()))
+        .json(serde_json::json!({ "data": csrf_token }))
+}
+
+/// Public key JWT (JWKS) supaya service lain bisa verifikasi session tanpa shared secret
+#[get("/.well-known/jwks.json")]
+pub async fn jwks() -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(KeyRing::get().jwks())
+}
+
+#[post("/passkeys/register/options")]
+async fn passkey_register_options(user: AuthenticatedUser) -> impl Responder {
+
+    let result: ActionResult<serde_json::Value, String> = WebauthnService::registration_options(&user.claims).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data
+        })),
+    }
+}
+
+#[post("/passkeys/register")]
+async fn passkey_register(user: AuthenticatedUser, request: web::Json<PasskeyRegisterRequest>) -> impl Responder {
+
+    let result: ActionResult<PasskeyInfo, String> = WebauthnService::finish_registration(&user.claims, request.into_inner()).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "message": response.message,
+            "data": response.data
+        })),
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[post("/passkeys/login/options")]
+async fn passkey_login_options(request: Option<web::Json<PasskeyLoginOptionsRequest>>) -> impl Responder {
+
+    let email = request.and_then(|request| request.into_inner().email);
+    let result: ActionResult<serde_json::Value, String> = WebauthnService::authentication_options(email).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data
+        })),
+    }
+}
+
+/// Passkey sudah mencakup kepemilikan perangkat + verifikasi user, jadi tidak diminta 2FA lagi
+#[post("/passkeys/login")]
+async fn passkey_login(req: HttpRequest, mode: web::Query<AuthModeQuery>, request: web::Json<PasskeyLoginRequest>) -> impl Responder {
+
+    let result: ActionResult<Claims, String> = WebauthnService::finish_authentication(request.into_inner(), &req, APP_NAME).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        ActionResult { result: true, data: Some(user), .. } => {
+            match start_session(user).await {
+                Ok((tokens, message)) => session_response(tokens, message, mode.token_mode()),
+                Err(response) => response,
+            }
+        },
+        response => HttpResponse::Unauthorized().json(serde_json::json!({ "error": response.message })), // Passkey tidak valid, HTTP 401
+    }
+}
+
+#[get("/passkeys")]
+async fn list_passkeys(user: AuthenticatedUser) -> impl Responder {
+
+    let result: ActionResult<Vec<PasskeyInfo>, String> = WebauthnService::list_credentials(user.claims.usernid).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.data.unwrap_or_default()
+        })),
+    }
+}
+
+#[post("/passkeys/{credential_id}/delete")]
+async fn delete_passkey(user: AuthenticatedUser, credential_id: web::Path<String>) -> impl Responder {
+
+    let result: ActionResult<String, String> = WebauthnService::delete_credential(user.claims.usernid, &credential_id).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::NotFound().json(serde_json::json!({ "error": response.message })),
+    }
+}
+
+#[post("/impersonation/stop")]
+async fn stop_impersonation(user: AuthenticatedUser) -> impl Responder {
+
+    let AuthenticatedUser { claims: session, token } = user;
+
+    let result: ActionResult<String, String> = ImpersonationService::stop(&session, &token).await;
+
+    match result {
+        response if response.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": response.error
+            }))
+        }, // Jika error, HTTP 500
+        response if response.result => HttpResponse::Ok().json(serde_json::json!({
+            "data": response.message
+        })),
+        response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })),
+    }
+}
@@ -1,6 +1,24 @@
-use actix_web::{get, web, HttpResponse, Responder, Scope};
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder, Scope};
 
-use crate::{middleware::model::{ActionResult, HeaderParams, ResultList, TableDataParams}, services::{data_service::DataService, generic_service::GenericService}};
+use crate::{middleware::{model::{ActionResult, HeaderParams, ResultList, TableDataParams}, permission::{api_key, authorize, authorize_api_key}}, services::{data_service::DataService, generic_service::GenericService}};
+
+/// Tabel yang dibaca website publik tanpa login, selain ini butuh permission `data.export`
+const PUBLIC_TABLES: [&str; 3] = ["notes", "skills", "portfolio"];
+
+/// API key butuh scope `data:read:<table>`, user butuh permission `data.export` untuk tabel non-publik
+async fn authorize_table(req: &HttpRequest, tablename: &str) -> Result<(), HttpResponse> {
+    let tablename = tablename.trim().to_lowercase();
+
+    if let Some(key) = api_key(req) {
+        return authorize_api_key(&key, &format!("data:read:{}", tablename)).await.map(|_| ());
+    }
+
+    if PUBLIC_TABLES.contains(&tablename.as_str()) {
+        return Ok(());
+    }
+
+    authorize(req, "data.export").await.map(|_| ())
+}
 
 
 pub fn data_scope() -> Scope {
@@ -15,7 +33,11 @@ pub fn config(cfg: &mut web::ServiceConfig) {
 }
 
 #[get("/header")]
-pub async fn get_header(params: web::Query<HeaderParams>) -> impl Responder {
+pub async fn get_header(req: HttpRequest, params: web::Query<HeaderParams>) -> impl Responder {
+
+    if let Err(response) = authorize_table(&req, &params.tablename).await {
+        return response;
+    }
 
     let result: ActionResult<Vec<serde_json::Value>, String> = DataService::get_header(params.into_inner().tablename).await;
 
@@ -33,7 +55,11 @@ pub async fn get_header(params: web::Query<HeaderParams>) -> impl Responder {
 }
 
 #[get("/table")]
-async fn get_table(params: web::Query<TableDataParams>) -> impl Responder {
+async fn get_table(req: HttpRequest, params: web::Query<TableDataParams>) -> impl Responder {
+
+    if let Err(response) = authorize_table(&req, &params.tablename).await {
+        return response;
+    }
 
     let cache_key = GenericService::make_cache_key(&params.tablename, &params);
 
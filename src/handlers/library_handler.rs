@@ -1,7 +1,7 @@
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder, Scope};
 use validator::Validate;
 
-use crate::{middleware::model::{ActionResult, NewNoteRequest, NewPortfolioRequest, NewSkillRequest, Notes, Portfolio, Skill, UpdateNoteRequest, UpdatePortfolioRequest, UpdateSkillRequest}, services::{generic_service::GenericService, library_service::LibraryService}};
+use crate::{middleware::{permission::RequirePermission, model::{ActionResult, NewNoteRequest, NewPortfolioRequest, NewSkillRequest, Notes, Portfolio, Skill, UpdateNoteRequest, UpdatePortfolioRequest, UpdateSkillRequest}}, services::{generic_service::GenericService, library_service::LibraryService}};
 
 pub fn library_scope() -> Scope {
     
@@ -20,7 +20,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(get_portfolio);
 }
 
-#[post("/create")]
+#[post("/create", wrap = "RequirePermission::new(\"library.write\")")]
 async fn create_libary(req: HttpRequest, request: web::Json<NewNoteRequest>) -> impl Responder {
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -45,7 +45,7 @@ async fn create_libary(req: HttpRequest, request: web::Json<NewNoteRequest>) ->
     HttpResponse::Ok().json(result)
 }
 
-#[post("/update")]
+#[post("/update", wrap = "RequirePermission::new(\"library.write\")")]
 async fn update_libary(req: HttpRequest, request: web::Json<UpdateNoteRequest>) -> impl Responder {
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -96,7 +96,7 @@ async fn get_library(slug: web::Path<String>) -> impl Responder {
     }
 }
 
-#[post("/create-skill")]
+#[post("/create-skill", wrap = "RequirePermission::new(\"library.write\")")]
 async fn create_skill(request: web::Json<NewSkillRequest>) -> impl Responder {
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -115,7 +115,7 @@ async fn create_skill(request: web::Json<NewSkillRequest>) -> impl Responder {
     HttpResponse::Ok().json(result)
 }
 
-#[post("/update-skill")]
+#[post("/update-skill", wrap = "RequirePermission::new(\"library.write\")")]
 async fn update_skill(request: web::Json<UpdateSkillRequest>) -> impl Responder {
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -163,7 +163,7 @@ async fn get_skill(skill_id: web::Path<i32>) -> impl Responder {
     }
 }
 
-#[post("/create-portfolio")]
+#[post("/create-portfolio", wrap = "RequirePermission::new(\"library.write\")")]
 async fn create_portfolio(request: web::Json<NewPortfolioRequest>) -> impl Responder {
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -182,7 +182,7 @@ async fn create_portfolio(request: web::Json<NewPortfolioRequest>) -> impl Respo
     HttpResponse::Ok().json(result)
 }
 
-#[post("/update-portfolio")]
+#[post("/update-portfolio", wrap = "RequirePermission::new(\"library.write\")")]
 async fn update_portfolio(request: web::Json<UpdatePortfolioRequest>) -> impl Responder {
     if let Err(err) = request.validate() {
         return HttpResponse::BadRequest().json(serde_json::json!({
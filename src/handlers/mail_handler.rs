@@ -28,6 +28,16 @@ pub async fn preview_template(query: web::Query<HashMap<String, String>>) -> imp
     request.insert("email".to_string(), "budi@example.com".to_string());
     request.insert("title".to_string(), "LAUNDERY".to_string());
     request.insert("otp_code".to_string(), "12345".to_string());
+    request.insert("expires_minutes".to_string(), "5".to_string());
+    request.insert("failed_attempts".to_string(), "5".to_string());
+    request.insert("lock_minutes".to_string(), "15".to_string());
+    request.insert("ip_address".to_string(), "127.0.0.1".to_string());
+    request.insert("changed_at".to_string(), "2025-01-01 10:00:00".to_string());
+    request.insert("expires_hours".to_string(), "24".to_string());
+    request.insert("new_email".to_string(), "budi.baru@example.com".to_string());
+    request.insert("notice".to_string(), "Ada permintaan untuk mengganti email akun Anda.".to_string());
+    request.insert("grace_days".to_string(), "30".to_string());
+    request.insert("deletion_date".to_string(), "01-01-2026".to_string());
 
     match MailService::preview(&template, &request) {
         Ok(html) => HttpResponse::Ok()
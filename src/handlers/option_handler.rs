@@ -1,5 +1,5 @@
 use actix_web::{get, web, HttpRequest, HttpResponse, Scope};
-use crate::{middleware::{jwt_session::{validate_jwt, Claims}, model::ActionResult}, services::{auth_service::AuthService, option_service::OptionService}};
+use crate::{middleware::{auth_extractor::OptionalUser, model::ActionResult, permission::{api_key, authorize_api_key}}, services::option_service::OptionService};
 
 pub fn option_scope() -> Scope {
     
@@ -12,10 +12,23 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .service(get_options);
 }
 
-const APP_NAME: &str = "snakesystem-api";
+/// Wajib login, atau API key dengan scope `options:read:<code>`
+async fn require_user_or_key(req: &HttpRequest, user: &OptionalUser, code: &str) -> Result<(), HttpResponse> {
+    if let Some(key) = api_key(req) {
+        return authorize_api_key(&key, &format!("options:read:{}", code.to_lowercase())).await.map(|_| ());
+    }
+
+    if user.0.is_none() {
+        return Err(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Token not found or session has expired"
+        })));
+    }
+
+    Ok(())
+}
 
 #[get("/{code}")]
-pub async fn get_options(req: HttpRequest, path: web::Path<String>, query: web::Query<std::collections::HashMap<String, String>>) -> HttpResponse {
+pub async fn get_options(req: HttpRequest, user: OptionalUser, path: web::Path<String>, query: web::Query<std::collections::HashMap<String, String>>) -> HttpResponse {
     let code = path.into_inner();
     let keyword = query.get("keyword").map(|s| s.as_str());
 
@@ -47,58 +60,9 @@ pub async fn get_options(req: HttpRequest, path: web::Path<String>, query: web::
         };
     }
 
-    // 3. Kalau bukan public_codes, wajib login → cek cookie + JWT + session
-    // Buat ActionResult<Claims, _> untuk menampung hasil sesi
-    let mut auth_res: ActionResult<Claims, String> = ActionResult::default();
-    // 3a. Ambil cookie "token" (nama cookie diasumsikan APP_NAME)
-    let token_cookie = req.cookie(APP_NAME);
-
-    let token = match token_cookie {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            auth_res.error = Some("Token not found".to_string());
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": auth_res.error
-            }));
-        }
-    };
-
-    // 3b. Validasi JWT; misal validate_jwt(&token) → Result<Claims, Err>
-    let claims = match validate_jwt(&token) {
-        Ok(claims) => claims,
-        Err(err) => {
-            auth_res.error = Some(err.to_string());
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": auth_res.error
-            }));
-        }
-    };
-
-    // 3c. Cek session lewat AuthService
-    // Asumsikan signature: check_session(claims: Claims, access: String, refresh: String, flag1: bool, flag2: bool, flag3: bool, cookie_name: &str)
-    auth_res = AuthService::check_session(
-        claims.clone(),
-        token.clone(),  // bisa saja kamu pakai token yang sama untuk access & refresh 
-        token.clone(),
-        false,
-        false,
-        true,
-        APP_NAME,
-    )
-    .await;
-
-    // 3d. Tangani response dari check_session
-    if let Some(err_msg) = &auth_res.error {
-        // Error di server
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": err_msg
-        }));
-    }
-    if !auth_res.result {
-        // Sesi tidak valid (misal token expired)
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": auth_res.message
-        }));
+    // 3. Kalau bukan public_codes, wajib login / API key
+    if let Err(response) = require_user_or_key(&req, &user, &code).await {
+        return response;
     }
 
     // Jika valid, lanjut panggil OptionService
@@ -122,63 +86,14 @@ pub async fn get_options(req: HttpRequest, path: web::Path<String>, query: web::
 }
 
 #[get("/city")]
-pub async fn get_options_city(req: HttpRequest, query: web::Query<std::collections::HashMap<String, String>>) -> HttpResponse {
-    let keyword = query.get("keyword").map(|s| s.as_str());
-
-    // 3. Kalau bukan public_codes, wajib login → cek cookie + JWT + session
-    // Buat ActionResult<Claims, _> untuk menampung hasil sesi
-    let mut auth_res: ActionResult<Claims, String> = ActionResult::default();
-    // 3a. Ambil cookie "token" (nama cookie diasumsikan APP_NAME)
-    let token_cookie = req.cookie(APP_NAME);
+pub async fn get_options_city(req: HttpRequest, user: OptionalUser, query: web::Query<std::collections::HashMap<String, String>>) -> HttpResponse {
 
-    let token = match token_cookie {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            auth_res.error = Some("Token not found".to_string());
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": auth_res.error
-            }));
-        }
-    };
-
-    // 3b. Validasi JWT; misal validate_jwt(&token) → Result<Claims, Err>
-    let claims = match validate_jwt(&token) {
-        Ok(claims) => claims,
-        Err(err) => {
-            auth_res.error = Some(err.to_string());
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": auth_res.error
-            }));
-        }
-    };
-
-    // 3c. Cek session lewat AuthService
-    // Asumsikan signature: check_session(claims: Claims, access: String, refresh: String, flag1: bool, flag2: bool, flag3: bool, cookie_name: &str)
-    auth_res = AuthService::check_session(
-        claims.clone(),
-        token.clone(),  // bisa saja kamu pakai token yang sama untuk access & refresh 
-        token.clone(),
-        false,
-        false,
-        true,
-        APP_NAME,
-    )
-    .await;
-
-    // 3d. Tangani response dari check_session
-    if let Some(err_msg) = &auth_res.error {
-        // Error di server
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": err_msg
-        }));
-    }
-    if !auth_res.result {
-        // Sesi tidak valid (misal token expired)
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": auth_res.message
-        }));
+    if let Err(response) = require_user_or_key(&req, &user, "city").await {
+        return response;
     }
 
+    let keyword = query.get("keyword").map(|s| s.as_str());
+
     // Jika valid, lanjut panggil OptionService
     let list_result: ActionResult<serde_json::Value, String> = OptionService::get_options_city(keyword).await;
 
@@ -200,55 +115,10 @@ pub async fn get_options_city(req: HttpRequest, query: web::Query<std::collectio
 }
 
 #[get("/npwp")]
-pub async fn get_question_npwp(req: HttpRequest) -> HttpResponse {
+pub async fn get_question_npwp(req: HttpRequest, user: OptionalUser) -> HttpResponse {
 
-    let mut auth_res: ActionResult<Claims, String> = ActionResult::default();
-    // 3a. Ambil cookie "token" (nama cookie diasumsikan APP_NAME)
-    let token_cookie = req.cookie(APP_NAME);
-
-    let token = match token_cookie {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            auth_res.error = Some("Token not found".to_string());
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": auth_res.error
-            }));
-        }
-    };
-
-    // 3b. Validasi JWT; misal validate_jwt(&token) → Result<Claims, Err>
-    let claims = match validate_jwt(&token) {
-        Ok(claims) => claims,
-        Err(err) => {
-            auth_res.error = Some(err.to_string());
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": auth_res.error
-            }));
-        }
-    };
-
-    auth_res = AuthService::check_session(
-        claims.clone(),
-        token.clone(),  // bisa saja kamu pakai token yang sama untuk access & refresh 
-        token.clone(),
-        false,
-        false,
-        true,
-        APP_NAME,
-    )
-    .await;
-
-    if let Some(err_msg) = &auth_res.error {
-        // Error di server
-        return HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": err_msg
-        }));
-    }
-    if !auth_res.result {
-        // Sesi tidak valid (misal token expired)
-        return HttpResponse::BadRequest().json(serde_json::json!({
-            "error": auth_res.message
-        }));
+    if let Err(response) = require_user_or_key(&req, &user, "npwp").await {
+        return response;
     }
 
     let list_result: ActionResult<Vec<serde_json::Value>, String> = OptionService::get_question_npwp().await;
@@ -1,8 +1,7 @@
-use actix_web::{get, web, HttpRequest, HttpResponse, Responder, Scope};
+use actix_web::{get, post, web, HttpResponse, Responder, Scope};
+use validator::Validate;
 
-use crate::{middleware::{jwt_session::validate_jwt, model::ActionResult}, services::{auth_service::AuthService, user_service::UserService}};
-
-const APP_NAME: &str = "snakesystem-api";
+use crate::{middleware::{auth_extractor::AuthenticatedUser, model::{ActionResult, DeleteAccountRequest, SecurityEventInfo, SecurityEventQuery}}, services::{account_service::AccountService, audit_service::AuditService, otp_service::OtpService, user_service::UserService}};
 
 pub fn user_scope() -> Scope {
     
@@ -10,65 +9,197 @@ pub fn user_scope() -> Scope {
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_user);
+    cfg.service(get_user)
+        .service(export_user)
+        .service(security_events)
+        .service(confirmation_code)
+        .service(delete_user)
+        .service(cancel_delete_user)
+        .service(confirm_delete_user);
 }
 
 #[get("/data")]
-async fn get_user(req: HttpRequest) -> impl Responder {
+async fn get_user(user: AuthenticatedUser) -> impl Responder {
+
+    let data = UserService::get_user(user.claims).await;
+
+    match data {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "data": res.data
+            }))
+        }
+        res => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
+    }
+}
 
-    let mut result: ActionResult<_, _> = ActionResult::default();
+#[get("/export")]
+async fn export_user(user: AuthenticatedUser) -> impl Responder {
 
-    // Ambil cookie "token"
-    let token_cookie = req.cookie(APP_NAME);
+    let usernid = user.claims.usernid;
+    let data = UserService::export_data(user.claims).await;
 
-    // Cek apakah token ada di cookie
-    let token = match token_cookie {
-        Some(cookie) => cookie.value().to_string(),
-        None => {
-            result.error = Some("Token not found".to_string());
-            return HttpResponse::Unauthorized().json(result);
+    match data {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok()
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"snakesystem-export-{}.json\"", usernid)))
+                .insert_header(("Cache-Control", "no-store"))
+                .json(serde_json::json!({
+                    "data": res.data
+                }))
         }
+        res => {
+            HttpResponse::NotFound().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
+    }
+}
+
+/// Riwayat keamanan akun sendiri, filter sama seperti `/admin/security-events` kecuali user
+#[get("/security-events")]
+async fn security_events(user: AuthenticatedUser, query: web::Query<SecurityEventQuery>) -> impl Responder {
+
+    let filter = SecurityEventQuery {
+        user_nid: Some(user.claims.usernid),
+        email: None,
+        ..query.into_inner()
     };
 
-    // Validate token
-    match validate_jwt(&token) {
-        Ok(claims) => {
-            result = AuthService::check_session(claims.clone(), token.clone(), token.clone(), false, false, true, APP_NAME).await;
-
-            match result {
-                response if response.error.is_some() => {
-                    HttpResponse::InternalServerError().json(serde_json::json!({
-                        "error": response.error
-                    }))
-                },
-                response if response.result => {
-
-                    let data = UserService::get_user(claims.clone()).await;
-                    
-                    match data {
-                        res if res.error.is_some() => {
-                            HttpResponse::InternalServerError().json(serde_json::json!({
-                                "error": res.error
-                            }))
-                        }
-                        res if res.result => {
-                            HttpResponse::Ok().json(serde_json::json!({
-                                "data": res.data
-                            }))
-                        }
-                        res => {
-                            HttpResponse::BadRequest().json(serde_json::json!({
-                                "error": res.message
-                            }))
-                        }
-                    }
-                },
-                response => HttpResponse::BadRequest().json(serde_json::json!({ "error": response.message })), // Jika gagal login, HTTP 400
-            }
-        },
-        Err(err) => {
-            result.error = Some(err.to_string());
-            HttpResponse::Unauthorized().json(serde_json::json!({ "error": result.error }))
-        },
+    let result: ActionResult<Vec<SecurityEventInfo>, String> = AuditService::search(filter).await;
+
+    match result {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "data": res.data.unwrap_or_default()
+            }))
+        }
+        res => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Kode konfirmasi via email untuk akun tanpa password (hapus akun / ganti email)
+#[post("/confirmation-code")]
+async fn confirmation_code(user: AuthenticatedUser) -> impl Responder {
+
+    let result: ActionResult<String, String> = OtpService::request_confirmation(user.claims.usernid).await;
+
+    match result {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "data": res.message
+            }))
+        }
+        res => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
+    }
+}
+
+#[post("/delete")]
+async fn delete_user(user: AuthenticatedUser, request: web::Json<DeleteAccountRequest>) -> impl Responder {
+
+    if let Err(err) = request.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": err
+        }));
+    }
+
+    let result: ActionResult<String, String> = AccountService::request_deletion(user.claims, request.into_inner()).await;
+
+    match result {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "data": res.message
+            }))
+        }
+        res => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
+    }
+}
+
+#[post("/delete/cancel")]
+async fn cancel_delete_user(user: AuthenticatedUser) -> impl Responder {
+
+    let result: ActionResult<String, String> = AccountService::cancel_deletion(user.claims).await;
+
+    match result {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "data": res.message
+            }))
+        }
+        res => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
+    }
+}
+
+#[post("/delete/confirm/{deletion_key}")]
+async fn confirm_delete_user(deletion_key: web::Path<String>) -> impl Responder {
+
+    let result: ActionResult<String, String> = AccountService::confirm_deletion(&deletion_key).await;
+
+    match result {
+        res if res.error.is_some() => {
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": res.error
+            }))
+        }
+        res if res.result => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "data": res.message
+            }))
+        }
+        res => {
+            HttpResponse::BadRequest().json(serde_json::json!({
+                "error": res.message
+            }))
+        }
+    }
+}
@@ -1,18 +1,17 @@
 use actix_cors::Cors;
 use actix_web::{get, http, web::{self, route, ServiceConfig}, Responder};
 use docs::swagger::{health_check, Swagger};
-use handlers::{auth_handler::auth_scope, mail_handler::mail_scope, option_handler::option_scope};
+use handlers::{auth_handler::{auth_scope, jwks}, mail_handler::mail_scope, option_handler::option_scope};
 use redis::Client;
-use services::generic_service::GenericService;
+use services::{generic_service::GenericService, job_service::JobService, oidc_service::OidcService};
 use shuttle_actix_web::ShuttleActixWeb;
 use shuttle_runtime::SecretStore;
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use once_cell::sync::OnceCell;
-use std::{collections::HashMap, sync::{Arc, Mutex}};
 
-use crate::{handlers::{data_handler::data_scope, library_handler::library_scope, user_handler::user_scope}, middleware::redis::redis_scope};
+use crate::{handlers::{admin_handler::admin_scope, api_key_handler::api_key_scope, data_handler::data_scope, library_handler::library_scope, user_handler::user_scope}, middleware::{crypto::EnvelopeKeys, csrf::CsrfProtection, jwt_session::KeyRing, password::init_password_hashing, redis::redis_scope}};
 
 pub static CONNECTION: OnceCell<PgPool> = OnceCell::new();
 pub static SECRETS: OnceCell<SecretStore> = OnceCell::new();
@@ -24,18 +23,18 @@ async fn hello_world() -> impl Responder {
     format!("Hello World! DB returns")
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    // now Clone because Arc is Clone
-    pub oauth_store: Arc<Mutex<HashMap<String, (oauth2::CsrfToken, oauth2::PkceCodeVerifier)>>>,
-}
-
 mod middleware {
+    pub mod auth_extractor;
     pub mod crypto;
+    pub mod csrf;
     pub mod jwt_session;
     pub mod socket;
     pub mod model;
+    pub mod oauth_state;
+    pub mod password;
+    pub mod permission;
     pub mod redis;
+    pub mod webauthn;
 }
 mod services {
     pub mod auth_service;
@@ -45,6 +44,19 @@ mod services {
     pub mod option_service;
     pub mod library_service;
     pub mod data_service;
+    pub mod login_guard_service;
+    pub mod mfa_service;
+    pub mod otp_service;
+    pub mod api_key_service;
+    pub mod role_service;
+    pub mod account_service;
+    pub mod audit_service;
+    pub mod token_revocation_service;
+    pub mod job_service;
+    pub mod webauthn_service;
+    pub mod oidc_service;
+    pub mod impersonation_service;
+    pub mod kyc_service;
 }
 mod handlers {
     pub mod auth_handler;
@@ -53,6 +65,8 @@ mod handlers {
     pub mod user_handler;
     pub mod library_handler;
     pub mod data_handler;
+    pub mod admin_handler;
+    pub mod api_key_handler;
 }
 mod utils {
     pub mod api_docs;
@@ -70,10 +84,6 @@ async fn main(
     let db_url = secrets.get("DATABASE_URL").expect("DB URL not found");
     let redis_url = secrets.get("REDIS_URL").expect("DB URL not found");
 
-    let state = AppState {
-        oauth_store: Arc::new(Mutex::new(HashMap::new())),
-    };
-
     let pool = match PgPoolOptions::new()
         .max_connections(10)
         .idle_timeout(std::time::Duration::from_secs(30))
@@ -97,31 +107,62 @@ async fn main(
     SECRETS.set(secrets.clone()).unwrap_or_else(|_| panic!("Failed to set SECRETS"));
     REDIS_CLIENT.set(redis_client).unwrap_or_else(|_| panic!("Failed to set REDIS_CLIENT"));
 
+    // Load key JWT sekarang supaya secret yang salah / hilang gagal saat deploy, bukan di request pertama
+    KeyRing::init();
+    EnvelopeKeys::init().unwrap_or_else(|e| panic!("Invalid encryption keys: {}", e));
+    init_password_hashing();
+    OidcService::init();
+
+    JobService::start();
+
+    // Cookie session ikut terkirim cross-site, jadi origin dibatasi: `CORS_ALLOWED_ORIGINS` (dipisah koma), default `FRONT_URL`
+    let allowed_origins: Vec<String> = secrets.get("CORS_ALLOWED_ORIGINS")
+        .or_else(|| secrets.get("FRONT_URL"))
+        .unwrap_or_default()
+        .split(',')
+        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect();
+    let allow_localhost = secrets.get("CORS_ALLOW_LOCALHOST").is_some_and(|value| value == "true");
+
     let config = move |cfg: &mut ServiceConfig| {
+        let allowed_origins = allowed_origins.clone();
         let cors = Cors::default()
-            .allow_any_origin()
+            .allowed_origin_fn(move |origin, _| {
+                let origin = origin.to_str().unwrap_or_default();
+                allowed_origins.iter().any(|allowed| allowed == origin)
+                    || (allow_localhost && (origin.starts_with("http://localhost") || origin.starts_with("http://127.0.0.1")))
+            })
             .allowed_methods(vec!["GET", "POST", "OPTIONS"])
-            .allowed_headers(vec![http::header::CONTENT_TYPE])
+            .allowed_headers(vec![
+                http::header::CONTENT_TYPE,
+                http::header::AUTHORIZATION,
+                http::header::HeaderName::from_static("x-api-key"),
+                http::header::HeaderName::from_static("x-csrf-token"),
+            ])
             .max_age(3600)
             .supports_credentials();
 
         cfg.service(health_check)
+            .service(jwks)
             .service(
                 web::scope("/api/v1")
-                    .wrap(cors)
+                    .wrap(CsrfProtection)
+                    .wrap(cors) // paling luar, supaya response 403 CSRF tetap membawa header CORS
                     .service(mail_scope())
                     .service(option_scope())
                     .service(auth_scope())
                     .service(library_scope())
                     .service(data_scope())
                     .service(redis_scope())
-                    .service(user_scope()),                   
+                    .service(user_scope())
+                    .service(admin_scope())
+                    .service(api_key_scope()),                   
             )
             .service(
                 SwaggerUi::new("/docs/{_:.*}")
                     .url("/api-docs/openapi.json", Swagger::openapi()),
             )
-            .app_data(web::Data::new(state.clone()))
             .app_data(web::JsonConfig::default().error_handler(GenericService::json_error_handler))
             .default_service(route().to(GenericService::not_found));
     };
@@ -0,0 +1,132 @@
+use std::{future::Future, pin::Pin};
+
+use actix_web::{dev::Payload, error::InternalError, http::{header, Method}, FromRequest, HttpRequest, HttpResponse};
+
+use crate::{middleware::jwt_session::{validate_jwt, Claims}, services::{auth_service::AuthService, impersonation_service::ImpersonationService, token_revocation_service::TokenRevocationService}};
+
+const APP_NAME: &str = "snakesystem-api";
+
+/// Request tulis yang tetap boleh dengan token impersonation
+const IMPERSONATION_ALLOWED_WRITES: [&str; 2] = [
+    "/api/v1/auth/impersonation/stop",
+    "/api/v1/auth/logout",
+];
+
+/// 🔑 User yang sudah login (bearer / cookie → JWT → baris `cookies`).
+/// Cukup jadikan argumen handler: `async fn handler(user: AuthenticatedUser)`, tanpa session langsung `401`.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub claims: Claims,
+    /// Access token yang dipakai request ini (untuk menandai session yang sedang aktif)
+    pub token: String,
+}
+
+/// Sama seperti [`AuthenticatedUser`] tapi tidak wajib login, `None` kalau tidak ada session yang valid
+#[derive(Debug, Clone)]
+pub struct OptionalUser(pub Option<AuthenticatedUser>);
+
+fn unauthorized(message: impl Into<String>) -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({ "error": message.into() }))
+}
+
+/// Ambil access token dari header `Authorization: Bearer <jwt>` (mobile / server-to-server),
+/// kalau tidak ada pakai cookie `snakesystem-api` (browser)
+pub fn request_token(req: &HttpRequest) -> Option<String> {
+    let bearer = req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+        .map(|(_, token)| token.trim().to_string())
+        .filter(|token| !token.is_empty());
+
+    bearer.or_else(|| {
+        req.cookie(APP_NAME)
+            .map(|cookie| cookie.value().to_string())
+            .filter(|token| !token.is_empty())
+    })
+}
+
+/// Alasan access token ditolak [`verify_token`]
+#[derive(Debug)]
+pub enum TokenError {
+    /// JWT tidak valid / expired
+    Invalid(String),
+    /// Ada di denylist Redis
+    Revoked,
+    Redis(redis::RedisError),
+}
+
+impl TokenError {
+    /// `401` untuk token yang ditolak, `500` kalau Redis bermasalah
+    pub fn to_response(&self) -> HttpResponse {
+        match self {
+            TokenError::Invalid(err) => unauthorized(err.clone()),
+            TokenError::Revoked => unauthorized("Token has been revoked"),
+            TokenError::Redis(e) => HttpResponse::InternalServerError().json(serde_json::json!({ "error": format!("Redis error: {}", e) })),
+        }
+    }
+}
+
+/// Validasi JWT + denylist Redis. Semua jalur yang menerima access token wajib lewat sini, bukan `validate_jwt` langsung.
+pub fn verify_token(token: &str) -> Result<Claims, TokenError> {
+    let claims = validate_jwt(token).map_err(|err| TokenError::Invalid(err.to_string()))?;
+
+    match TokenRevocationService::is_revoked(&claims) {
+        Ok(false) => Ok(claims),
+        Ok(true) => Err(TokenError::Revoked),
+        Err(e) => Err(TokenError::Redis(e)),
+    }
+}
+
+/// Cek session dari bearer token / cookie dan catat aktivitas terakhir di tabel `cookies`
+pub async fn authenticate(req: &HttpRequest) -> Result<AuthenticatedUser, HttpResponse> {
+    let token = match request_token(req) {
+        Some(token) => token,
+        None => return Err(unauthorized("Token not found")),
+    };
+
+    let claims = verify_token(&token).map_err(|err| err.to_response())?;
+
+    // Impersonation hanya untuk melihat, semua request tulis ditolak dan dicatat
+    if claims.impersonator.is_some()
+        && !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        && !IMPERSONATION_ALLOWED_WRITES.contains(&req.path()) {
+            ImpersonationService::record_blocked_write(&claims, req).await;
+            return Err(HttpResponse::Forbidden().json(serde_json::json!({ "error": "Write actions are not allowed while impersonating" })));
+        }
+
+    let result = AuthService::check_session(claims.clone(), token.clone(), token.clone(), false, true, true, APP_NAME).await;
+
+    if let Some(err) = result.error {
+        return Err(unauthorized(err));
+    }
+
+    Ok(AuthenticatedUser { claims, token })
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+
+        Box::pin(async move {
+            authenticate(&req)
+                .await
+                .map_err(|response| InternalError::from_response("Unauthorized", response).into())
+        })
+    }
+}
+
+impl FromRequest for OptionalUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+
+        Box::pin(async move { Ok(OptionalUser(authenticate(&req).await.ok())) })
+    }
+}
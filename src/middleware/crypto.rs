@@ -1,6 +1,11 @@
+use std::fmt;
+
 use aes::Aes256;
+use aes_gcm::{aead::{Aead, KeyInit, Payload}, Aes256Gcm, Nonce};
 use ctr::cipher::{KeyIvInit, StreamCipher};
-use base64::{engine::general_purpose::URL_SAFE, Engine as _};
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE, URL_SAFE_NO_PAD}, Engine as _};
+use once_cell::sync::OnceCell;
+use rand::RngCore;
 use sha2::{Digest, Sha256};
 
 use crate::SECRETS; // Import SHA-256 untuk hashing
@@ -53,4 +58,289 @@ pub fn decrypt_text(encrypted_text: &str, original_text: &str) -> String {
     cipher.apply_keystream(&mut decrypted_data);
 
     String::from_utf8(decrypted_data).expect("Invalid UTF-8")
-}
\ No newline at end of file
+}
+
+/// #️⃣ Hash SHA-256 (hex) untuk token opaque yang disimpan di database (refresh token, dll)
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Key id untuk `CRYPTO_SECRET`, supaya data lama tetap bisa dibuka setelah `CRYPTO_KEYS` diisi
+const LEGACY_KEY_ID: &str = "k0";
+
+/// 🔑 Kunci envelope: `CRYPTO_KEYS` (`<kid>:<base64 32 byte>` dipisah koma) ditambah `k0` = `CRYPTO_SECRET`.
+///
+/// Dibaca sekali dari `SECRETS` ([`EnvelopeKeys::init`] di `main`), enkripsi baru memakai `CRYPTO_ACTIVE_KEY`
+/// (default kunci pertama di `CRYPTO_KEYS`, atau `k0`). Envelope `v1` lama selalu memakai `CRYPTO_SECRET`.
+pub struct EnvelopeKeys {
+    active_kid: String,
+    keys: Vec<(String, Aes256Gcm)>,
+    legacy: Aes256Gcm,
+}
+
+static ENVELOPE_KEYS: OnceCell<EnvelopeKeys> = OnceCell::new();
+
+impl EnvelopeKeys {
+    /// Dipanggil sekali dari `main` setelah `SECRETS` siap, supaya key yang salah gagal saat deploy, bukan saat request
+    pub fn init() -> Result<(), String> {
+        Self::get().map(|_| ())
+    }
+
+    pub fn get() -> Result<&'static EnvelopeKeys, String> {
+        ENVELOPE_KEYS.get_or_try_init(|| {
+            let secrets = SECRETS.get().ok_or("SECRETS not initialized")?;
+            let crypto_secret = secrets.get("CRYPTO_SECRET").ok_or("CRYPTO_SECRET was not found")?;
+            Self::parse(&crypto_secret, &secrets.get("CRYPTO_KEYS").unwrap_or_default(), secrets.get("CRYPTO_ACTIVE_KEY").as_deref())
+        })
+    }
+
+    pub fn parse(crypto_secret: &str, crypto_keys: &str, active_kid: Option<&str>) -> Result<Self, String> {
+        let legacy = Aes256Gcm::new_from_slice(crypto_secret.as_bytes()).map_err(|_| "CRYPTO_SECRET must be 32 bytes")?;
+
+        let mut keys: Vec<(String, Aes256Gcm)> = Vec::new();
+        for entry in crypto_keys.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (kid, key) = entry.split_once(':').ok_or("CRYPTO_KEYS entry must be <kid>:<base64 key>")?;
+            let kid = kid.trim();
+
+            // Key id dipakai di envelope (dipisah `.`) dan di pola LIKE, jadi hanya huruf, angka, `-`
+            if kid.is_empty() || !kid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err("CRYPTO_KEYS key id must be alphanumeric".to_string());
+            }
+            if keys.iter().any(|(id, _)| id == kid) {
+                return Err(format!("CRYPTO_KEYS key id {} is listed twice", kid));
+            }
+
+            let key = STANDARD.decode(key.trim()).map_err(|_| format!("CRYPTO_KEYS key {} must be base64", kid))?;
+            let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| format!("CRYPTO_KEYS key {} must be 32 bytes", kid))?;
+            keys.push((kid.to_string(), cipher));
+        }
+
+        if !keys.iter().any(|(kid, _)| kid == LEGACY_KEY_ID) {
+            keys.push((LEGACY_KEY_ID.to_string(), legacy.clone()));
+        }
+
+        let active_kid = match active_kid.map(str::trim).filter(|kid| !kid.is_empty()) {
+            Some(kid) => kid.to_string(),
+            None => keys[0].0.clone(),
+        };
+
+        if !keys.iter().any(|(kid, _)| *kid == active_kid) {
+            return Err(format!("CRYPTO_ACTIVE_KEY {} is not listed in CRYPTO_KEYS", active_kid));
+        }
+
+        Ok(Self { active_kid, keys, legacy })
+    }
+
+    /// Key id untuk enkripsi baru
+    pub fn active_kid(&self) -> &str {
+        &self.active_kid
+    }
+
+    fn cipher(&self, kid: &str) -> Option<&Aes256Gcm> {
+        self.keys.iter().find(|(id, _)| id == kid).map(|(_, cipher)| cipher)
+    }
+
+    /// 🔐 Enkripsi AES-256-GCM dengan nonce acak, hasil `v2.<kid>.<base64url(nonce || ciphertext)>`.
+    /// `context` (mis. `user_kyc.npwp_number:42`) ikut diautentikasi sebagai AAD, jadi ciphertext tidak bisa dipindah ke kolom / baris lain.
+    pub fn seal(&self, plain_text: &str, context: &str) -> Result<String, String> {
+        let cipher = self.cipher(&self.active_kid).ok_or("Active key is missing")?;
+
+        let mut nonce = [0u8; 12];
+        rand::rng().fill_bytes(&mut nonce);
+
+        let aad = format!("v2.{}.{}", self.active_kid, context);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plain_text.as_bytes(), aad: aad.as_bytes() })
+            .map_err(|_| "AES-GCM encryption failed")?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!("v2.{}.{}", self.active_kid, URL_SAFE_NO_PAD.encode(payload)))
+    }
+
+    /// 🔓 Dekripsi envelope `v2` (dan `v1` lama tanpa context). Pesan error tidak pernah berisi isi data.
+    pub fn open(&self, sealed: &str, context: &str) -> Result<Redacted<String>, String> {
+        let (cipher, encoded, aad) = match sealed.split_once('.') {
+            Some(("v1", encoded)) => (&self.legacy, encoded, String::new()),
+            Some(("v2", rest)) => {
+                let (kid, encoded) = rest.split_once('.').ok_or("Malformed envelope")?;
+                let cipher = self.cipher(kid).ok_or_else(|| format!("Unknown key id {}", kid))?;
+                (cipher, encoded, format!("v2.{}.{}", kid, context))
+            }
+            _ => return Err("Unsupported envelope version".to_string()),
+        };
+
+        let payload = URL_SAFE_NO_PAD.decode(encoded).map_err(|_| "Malformed envelope")?;
+        if payload.len() < 12 {
+            return Err("Malformed envelope".to_string());
+        }
+
+        let (nonce, ciphertext) = payload.split_at(12);
+        let plain = cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| "Authentication failed")?;
+
+        String::from_utf8(plain).map(Redacted::new).map_err(|_| "Invalid UTF-8".to_string())
+    }
+}
+
+/// Key id untuk enkripsi baru: `CRYPTO_ACTIVE_KEY`, default kunci pertama di `CRYPTO_KEYS` (atau `k0`)
+pub fn active_key_id() -> Result<String, String> {
+    EnvelopeKeys::get().map(|keys| keys.active_kid().to_string())
+}
+
+/// 🙈 Nilai hasil dekripsi: `Debug` / `Display` selalu `[REDACTED]` supaya tidak bocor ke log, isi asli lewat `expose()`
+#[derive(Clone)]
+pub struct Redacted<T>(T);
+
+impl<T> Redacted<T> {
+    pub fn new(value: T) -> Self {
+        Redacted(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+/// 🔐 [`EnvelopeKeys::seal`] dengan kunci dari `SECRETS`
+pub fn seal(plain_text: &str, context: &str) -> Result<String, String> {
+    EnvelopeKeys::get()?.seal(plain_text, context)
+}
+
+/// 🔓 [`EnvelopeKeys::open`] dengan kunci dari `SECRETS`
+pub fn open(sealed: &str, context: &str) -> Result<Redacted<String>, String> {
+    EnvelopeKeys::get()?.open(sealed, context)
+}
+
+/// Nilai sudah berupa envelope (bukan plaintext lama)
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with("v1.") || value.starts_with("v2.")
+}
+
+/// 🔐 [`seal`] tanpa context
+pub fn seal_text(plain_text: &str) -> Result<String, String> {
+    seal(plain_text, "")
+}
+
+/// 🔓 [`open`] tanpa context, `None` kalau format salah atau data sudah diubah
+pub fn open_text(sealed: &str) -> Option<String> {
+    open(sealed, "").ok().map(Redacted::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRYPTO_SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn key(byte: u8) -> String {
+        STANDARD.encode([byte; 32])
+    }
+
+    fn keys(crypto_keys: &str, active_kid: Option<&str>) -> EnvelopeKeys {
+        EnvelopeKeys::parse(CRYPTO_SECRET, crypto_keys, active_kid).unwrap()
+    }
+
+    #[test]
+    fn v2_round_trip() {
+        let keys = keys(&format!("k1:{}", key(1)), None);
+
+        let sealed = keys.seal("3171234567890001", "user_kyc.idcard_number:42").unwrap();
+        assert!(sealed.starts_with("v2.k1."));
+        assert!(!sealed.contains("3171234567890001"));
+        assert_eq!(keys.open(&sealed, "user_kyc.idcard_number:42").unwrap().expose(), "3171234567890001");
+
+        // Nonce acak, plaintext yang sama menghasilkan envelope berbeda
+        assert_ne!(sealed, keys.seal("3171234567890001", "user_kyc.idcard_number:42").unwrap());
+    }
+
+    #[test]
+    fn v1_round_trip_uses_crypto_secret() {
+        let keys = keys(&format!("k1:{}", key(1)), None);
+
+        let mut nonce = [0u8; 12];
+        rand::rng().fill_bytes(&mut nonce);
+        let ciphertext = Aes256Gcm::new_from_slice(CRYPTO_SECRET.as_bytes()).unwrap()
+            .encrypt(Nonce::from_slice(&nonce), "JBSWY3DPEHPK3PXP".as_bytes())
+            .unwrap();
+        let sealed = format!("v1.{}", URL_SAFE_NO_PAD.encode([nonce.as_slice(), &ciphertext].concat()));
+
+        assert_eq!(keys.open(&sealed, "").unwrap().expose(), "JBSWY3DPEHPK3PXP");
+        // v1 tidak punya context, context apa pun diabaikan
+        assert_eq!(keys.open(&sealed, "user_kyc.npwp_number:1").unwrap().expose(), "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn rejects_context_mismatch() {
+        let keys = keys("", None);
+        let sealed = keys.seal("1234567890", "user_kyc.npwp_number:42").unwrap();
+
+        assert_eq!(keys.open(&sealed, "user_kyc.npwp_number:43").err(), Some("Authentication failed".to_string()));
+        assert_eq!(keys.open(&sealed, "user_kyc.bank_account_number:42").err(), Some("Authentication failed".to_string()));
+    }
+
+    #[test]
+    fn rejects_unknown_key_id_and_tampered_envelope() {
+        let old = keys(&format!("k1:{}", key(1)), None);
+        let sealed = old.seal("secret", "ctx").unwrap();
+
+        let rotated = keys(&format!("k2:{}", key(2)), None);
+        assert_eq!(rotated.open(&sealed, "ctx").err(), Some("Unknown key id k1".to_string()));
+
+        // Key id diganti ke key lain yang dikenal tetap gagal karena key id ikut AAD
+        let both = keys(&format!("k2:{},k1:{}", key(2), key(1)), None);
+        assert!(both.open(&sealed.replacen("v2.k1.", "v2.k2.", 1), "ctx").is_err());
+
+        assert_eq!(both.open("v3.k1.AAAA", "ctx").err(), Some("Unsupported envelope version".to_string()));
+        assert_eq!(both.open("v2.k1.AAAA", "ctx").err(), Some("Malformed envelope".to_string()));
+        assert_eq!(both.open("plaintext", "ctx").err(), Some("Unsupported envelope version".to_string()));
+    }
+
+    #[test]
+    fn rotation_keeps_old_envelopes_readable() {
+        let old = keys(&format!("k1:{}", key(1)), None);
+        let sealed = old.seal("secret", "ctx").unwrap();
+        let legacy = keys("", None).seal("legacy", "ctx").unwrap();
+        assert!(legacy.starts_with("v2.k0."));
+
+        let rotated = keys(&format!("k1:{},k2:{}", key(1), key(2)), Some("k2"));
+        assert_eq!(rotated.active_kid(), "k2");
+        assert_eq!(rotated.open(&sealed, "ctx").unwrap().expose(), "secret");
+        assert_eq!(rotated.open(&legacy, "ctx").unwrap().expose(), "legacy");
+        assert!(rotated.seal("new", "ctx").unwrap().starts_with("v2.k2."));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_configuration() {
+        assert!(EnvelopeKeys::parse("too-short", "", None).is_err());
+        assert!(EnvelopeKeys::parse(CRYPTO_SECRET, "k1", None).is_err());
+        assert!(EnvelopeKeys::parse(CRYPTO_SECRET, &format!("k.1:{}", key(1)), None).is_err());
+        assert!(EnvelopeKeys::parse(CRYPTO_SECRET, "k1:not-base64!", None).is_err());
+        assert!(EnvelopeKeys::parse(CRYPTO_SECRET, &format!("k1:{}", STANDARD.encode([1u8; 16])), None).is_err());
+        assert!(EnvelopeKeys::parse(CRYPTO_SECRET, &format!("k1:{},k1:{}", key(1), key(2)), None).is_err());
+        assert!(EnvelopeKeys::parse(CRYPTO_SECRET, &format!("k1:{}", key(1)), Some("k9")).is_err());
+    }
+
+    #[test]
+    fn redacted_never_prints_value() {
+        let value = Redacted::new("3171234567890001".to_string());
+        assert_eq!(format!("{} {:?}", value, value), "[REDACTED] [REDACTED]");
+    }
+}
@@ -0,0 +1,119 @@
+use std::{future::{ready, Future, Ready}, pin::Pin};
+
+use actix_web::{body::EitherBody, cookie::{time, Cookie, SameSite}, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, http::{header, Method}, Error, HttpResponse};
+
+use crate::{middleware::password::constant_time_eq, services::generic_service::GenericService};
+
+pub const CSRF_COOKIE: &str = "snakesystem-csrf";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Cookie yang membawa credential otomatis dari browser
+const SESSION_COOKIES: [&str; 2] = ["snakesystem-api", "snakesystem-api-refresh"];
+
+/// Endpoint sebelum login yang tidak memakai cookie session, tetap boleh walau browser masih menyimpan cookie lama
+const EXEMPT_PATHS: [&str; 11] = [
+    "/api/v1/auth/login",
+    "/api/v1/auth/register",
+    "/api/v1/auth/otp/",
+    "/api/v1/auth/2fa/verify",
+    "/api/v1/auth/passkeys/login/options",
+    "/api/v1/auth/passkeys/login",
+    "/api/v1/auth/activation/",
+    "/api/v1/auth/reset-password",
+    "/api/v1/auth/change-password",
+    "/api/v1/auth/email/confirm/",
+    "/api/v1/user/delete/confirm/",
+];
+
+pub fn new_csrf_token() -> String {
+    GenericService::random_string(48)
+}
+
+/// Cookie CSRF sengaja tidak `HttpOnly`: frontend satu domain bisa membacanya, frontend beda domain
+/// memakai nilai `csrf_token` dari body login / `GET /auth/csrf`
+pub fn csrf_cookie(value: String, max_age: chrono::Duration) -> Cookie<'static> {
+    Cookie::build(CSRF_COOKIE, value)
+        .path("/")
+        .http_only(false)
+        .same_site(SameSite::None)
+        .secure(true)
+        .expires(time::OffsetDateTime::now_utc() + time::Duration::seconds(max_age.num_seconds()))
+        .finish()
+}
+
+/// 🛡️ Double-submit CSRF: request yang mengubah data dan membawa cookie session wajib mengirim header
+/// `X-CSRF-Token` yang sama dengan cookie `snakesystem-csrf`.
+/// Request dengan `Authorization: Bearer` atau `X-Api-Key` dikecualikan karena browser tidak mengirimnya otomatis.
+#[derive(Default)]
+pub struct CsrfProtection;
+
+impl<S, B> Transform<S, ServiceRequest> for CsrfProtection
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CsrfProtectionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfProtectionMiddleware { service }))
+    }
+}
+
+pub struct CsrfProtectionMiddleware<S> {
+    service: S,
+}
+
+fn requires_check(req: &ServiceRequest) -> bool {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE) {
+        return false;
+    }
+
+    let path = req.path();
+    if EXEMPT_PATHS.iter().any(|exempt| path == *exempt || (exempt.ends_with('/') && path.starts_with(exempt))) {
+        return false;
+    }
+
+    let headers = req.headers();
+    let bearer = headers.get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.len() > 7 && value[..7].eq_ignore_ascii_case("Bearer "));
+
+    if bearer || headers.contains_key("X-Api-Key") {
+        return false;
+    }
+
+    SESSION_COOKIES.iter().any(|name| req.cookie(name).is_some_and(|cookie| !cookie.value().is_empty()))
+}
+
+fn token_matches(req: &ServiceRequest) -> bool {
+    let cookie = req.cookie(CSRF_COOKIE).map(|cookie| cookie.value().to_string()).unwrap_or_default();
+    let header = req.headers().get(CSRF_HEADER).and_then(|value| value.to_str().ok()).unwrap_or_default();
+
+    !cookie.is_empty() && constant_time_eq(cookie.as_bytes(), header.trim().as_bytes())
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfProtectionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if requires_check(&req) && !token_matches(&req) {
+            let response = HttpResponse::Forbidden().json(serde_json::json!({ "error": "Invalid CSRF token" }));
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
@@ -1,11 +1,99 @@
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine as _};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use chrono::{Duration, Utc};
 use utoipa::ToSchema;
 
-use crate::SECRETS;
+use crate::{services::generic_service::GenericService, SECRETS};
+
+/// Prefix DER SubjectPublicKeyInfo untuk Ed25519 (OID 1.3.101.112), diikuti 32 byte public key
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// 🔑 Kumpulan key EdDSA untuk sign & verify JWT.
+///
+/// Secret yang dibaca:
+/// - `JWT_SIGNING_KID`: kid yang dipakai untuk sign token baru
+/// - `JWT_KEY_IDS`: daftar kid (dipisah koma) yang masih diterima saat verifikasi
+/// - `JWT_PRIVATE_KEY_<kid>`: private key Ed25519 PKCS#8 PEM (cukup untuk kid aktif)
+/// - `JWT_PUBLIC_KEY_<kid>`: public key Ed25519 SPKI PEM
+pub struct KeyRing {
+    signing_kid: String,
+    signing_key: EncodingKey,
+    verifying_keys: HashMap<String, DecodingKey>,
+    jwks: Vec<serde_json::Value>,
+}
+
+static KEYRING: OnceCell<KeyRing> = OnceCell::new();
+
+fn pem_to_der(pem: &str) -> Vec<u8> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+    STANDARD.decode(body).expect("Invalid PEM body")
+}
+
+impl KeyRing {
+    pub fn get() -> &'static KeyRing {
+        KEYRING.get_or_init(Self::load)
+    }
+
+    /// Dipanggil sekali dari `main` setelah `SECRETS` siap, panic kalau konfigurasi key tidak valid
+    pub fn init() {
+        Self::get();
+    }
+
+    fn load() -> KeyRing {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let signing_kid = secrets.get("JWT_SIGNING_KID").expect("secret was not found");
+        let key_ids = secrets.get("JWT_KEY_IDS").unwrap_or_else(|| signing_kid.clone());
+
+        let private_pem = secrets
+            .get(&format!("JWT_PRIVATE_KEY_{}", signing_kid))
+            .expect("signing key was not found");
+        let signing_key = EncodingKey::from_ed_pem(private_pem.as_bytes()).expect("Invalid Ed25519 private key");
+
+        let mut verifying_keys = HashMap::new();
+        let mut jwks = Vec::new();
 
-// const SECRET_KEY: &str = "supersecretkey"; // 🔥 Ganti dengan key yang lebih aman!
+        for kid in key_ids.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            let public_pem = secrets
+                .get(&format!("JWT_PUBLIC_KEY_{}", kid))
+                .expect("verification key was not found");
+            let der = pem_to_der(&public_pem);
+
+            if der.len() != 44 || der[..12] != ED25519_SPKI_PREFIX {
+                panic!("JWT_PUBLIC_KEY_{} is not an Ed25519 public key", kid);
+            }
+
+            let x = URL_SAFE_NO_PAD.encode(&der[12..]);
+            verifying_keys.insert(kid.to_string(), DecodingKey::from_ed_components(&x).expect("Invalid Ed25519 public key"));
+            jwks.push(serde_json::json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "alg": "EdDSA",
+                "use": "sig",
+                "kid": kid,
+                "x": x,
+            }));
+        }
+
+        if !verifying_keys.contains_key(&signing_kid) {
+            panic!("JWT_KEY_IDS must contain JWT_SIGNING_KID ({})", signing_kid);
+        }
+
+        KeyRing { signing_kid, signing_key, verifying_keys, jwks }
+    }
+
+    /// Public key dalam format JWK Set untuk `/.well-known/jwks.json`
+    pub fn jwks(&self) -> serde_json::Value {
+        serde_json::json!({ "keys": self.jwks })
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Claims {
@@ -22,11 +110,63 @@ pub struct Claims {
     pub comp_name: Option<String>,
     pub ip_address: Option<String>,
     pub app_name: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// ID unik per token, dipakai denylist Redis saat logout / revoke
+    #[serde(default)]
+    pub jti: String,
+    /// Waktu token dibuat (unix timestamp), dipakai revoke "semua token sebelum waktu X"
+    #[serde(default)]
+    pub iat: usize,
+    /// Diisi ID admin saat token dipakai untuk impersonation, semua request tulis ditolak
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<i32>,
+}
+
+/// ⏳ Umur access token (JWT), default 15 menit, bisa diatur lewat secret `ACCESS_TOKEN_MINUTES`
+pub fn access_token_ttl() -> Duration {
+    let secrets = SECRETS.get().expect("SECRETS not initialized");
+    let minutes = secrets.get("ACCESS_TOKEN_MINUTES").and_then(|v| v.parse().ok()).unwrap_or(15);
+    Duration::minutes(minutes)
+}
+
+/// ⏳ Umur refresh token, default 7 hari, bisa diatur lewat secret `REFRESH_TOKEN_DAYS`
+pub fn refresh_token_ttl() -> Duration {
+    let secrets = SECRETS.get().expect("SECRETS not initialized");
+    let days = secrets.get("REFRESH_TOKEN_DAYS").and_then(|v| v.parse().ok()).unwrap_or(7);
+    Duration::days(days)
+}
+
+/// ⏳ Batas session tanpa aktivitas (`cookies.last_update`), default 60 menit, `SESSION_IDLE_MINUTES=0` untuk mematikan
+pub fn session_idle_timeout() -> Option<Duration> {
+    let secrets = SECRETS.get().expect("SECRETS not initialized");
+    let minutes: i64 = secrets.get("SESSION_IDLE_MINUTES").and_then(|v| v.parse().ok()).unwrap_or(60);
+    (minutes > 0).then(|| Duration::minutes(minutes))
+}
+
+/// ⏳ Umur maksimal session sejak login walau terus aktif, default 30 hari, bisa diatur lewat secret `SESSION_MAX_DAYS`
+pub fn session_max_age() -> Duration {
+    let secrets = SECRETS.get().expect("SECRETS not initialized");
+    let days = secrets.get("SESSION_MAX_DAYS").and_then(|v| v.parse().ok()).unwrap_or(30);
+    Duration::days(days)
 }
 
 impl Claims {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+
     pub fn new(user: Claims) -> Self {
-        let expired_token = Utc::now() + Duration::days(2); // Token berlaku 2 hari
+        Self::with_ttl(user, access_token_ttl()) // Token berumur pendek, diperpanjang lewat refresh token
+    }
+
+    pub fn with_ttl(user: Claims, ttl: Duration) -> Self {
+        let issued_at = Utc::now();
+        let expired_token = issued_at + ttl;
         let expired_date = expired_token.format("%Y-%m-%d %H:%M:%S").to_string();
         let exp = expired_token.timestamp() as usize; // ⏳ Set exp untuk validasi JWT
 
@@ -44,33 +184,64 @@ impl Claims {
             comp_name: user.comp_name,
             ip_address: user.ip_address,
             app_name: user.app_name,
+            user_agent: user.user_agent,
+            roles: user.roles,
+            permissions: user.permissions,
+            jti: GenericService::random_string(32),
+            iat: issued_at.timestamp() as usize,
+            impersonator: user.impersonator,
         }
     }
 }
 
 // 🔥 Generate JWT Token
-pub fn create_jwt(user: Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    let secrets = SECRETS.get().expect("SECRETS not initialized");
-    let secret_key = secrets.get("JWT_SECRET").expect("secret was not found");
-    let claims = Claims::new(user); // 🔥 Clone user di sini
-    let mut header = Header::default();
-    header.alg = Algorithm::HS256; // ✅ Set eksplisit algoritma HS256
+/// Return token + claims yang benar-benar di-encode (`jti` / `exp` yang dikirim ke client harus sama dengan token)
+pub fn create_jwt(user: Claims) -> Result<(String, Claims), jsonwebtoken::errors::Error> {
+    sign_claims(Claims::new(user))
+}
+
+/// Sama seperti [`create_jwt`] dengan umur token sendiri (token impersonation)
+pub fn create_jwt_with_ttl(user: Claims, ttl: Duration) -> Result<(String, Claims), jsonwebtoken::errors::Error> {
+    sign_claims(Claims::with_ttl(user, ttl))
+}
+
+fn sign_claims(claims: Claims) -> Result<(String, Claims), jsonwebtoken::errors::Error> {
+    let keyring = KeyRing::get();
+    let mut header = Header::new(Algorithm::EdDSA); // ✅ Asimetris, verifier cukup pegang public key
+    header.kid = Some(keyring.signing_kid.clone());
     let token = encode(
         &header,
         &claims,
-        &EncodingKey::from_secret(secret_key.as_bytes()),
+        &keyring.signing_key,
     )?;
-    Ok(token)
+    Ok((token, claims))
 }
 
 // 🔥 Validate JWT Token
 pub fn validate_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let secrets = SECRETS.get().expect("SECRETS not initialized");
-    let secret_key = secrets.get("JWT_SECRET").expect("secret was not found");
+    let keyring = KeyRing::get();
+
+    // Pilih public key berdasarkan `kid` supaya rotasi key tidak memutus token lama
+    let decoding_key = match decode_header(token) {
+        Ok(header) => match header.kid.as_deref().and_then(|kid| keyring.verifying_keys.get(kid)) {
+            Some(key) => key,
+            None => {
+                println!("❌ JWT Validation Error: unknown kid {:?}", header.kid);
+                return Err(jsonwebtoken::errors::Error::from(
+                    jsonwebtoken::errors::ErrorKind::InvalidToken,
+                ));
+            }
+        },
+        Err(err) => {
+            println!("❌ JWT Validation Error: {:?}", err);
+            return Err(err);
+        }
+    };
+
     match decode::<Claims>(
         token,
-        &DecodingKey::from_secret(secret_key.as_bytes()),
-        &Validation::new(Algorithm::HS256),
+        decoding_key,
+        &Validation::new(Algorithm::EdDSA),
     ) {
         Ok(token_data) => {
             let claims = token_data.claims;
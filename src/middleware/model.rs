@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
+use crate::middleware::jwt_session::Claims;
 use crate::utils::validation::validator::{
     required, valid_phone_number, valid_name, required_int, valid_password
 }; 
@@ -36,6 +37,157 @@ where
     serializer.serialize_str(&formatted)
 }
 
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct RoleInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct AssignRoleRequest {
+    #[validate(custom(function = "required"))]
+    pub role: String,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct ApiKeyInfo {
+    pub api_key_id: i32,
+    /// User pemilik key (key yang dibuat lewat API key lain mewarisi pemilik key tersebut)
+    #[serde(skip)]
+    pub created_by: Option<i32>,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateApiKeyRequest {
+    #[validate(custom(function = "required"))]
+    pub name: String,
+
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+
+    /// Kosong = tidak pernah expired
+    #[validate(range(max = 3650, message = "expires_in_days must be at most 3650"))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Satu baris session (device) di tabel `cookies`
+/// Cabut semua token user yang dibuat sebelum `before` (`YYYY-MM-DD HH:MM:SS`, waktu Jakarta), default sekarang
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevokeTokensRequest {
+    pub before: Option<String>,
+}
+
+/// Alasan impersonation wajib diisi, ikut tercatat di audit log
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ImpersonateRequest {
+    #[validate(length(min = 5, max = 500, message = "Reason must be between 5 and 500 characters"))]
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct UserSession {
+    pub session_id: String,
+    pub app_name: Option<String>,
+    pub ip_address: Option<String>,
+    pub device: Option<String>,
+    pub created_at: Option<String>,
+    pub last_activity: Option<String>,
+    pub current: bool,
+}
+
+/// Bagian `response` dari `PublicKeyCredential.toJSON()` di browser, semua binary dalam base64url
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasskeyCredentialResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    /// Hanya saat registrasi
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: Option<String>,
+    /// Hanya saat login
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: Option<String>,
+    pub signature: Option<String>,
+    #[serde(rename = "userHandle")]
+    pub user_handle: Option<String>,
+    #[serde(default)]
+    pub transports: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasskeyCredential {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub response: PasskeyCredentialResponse,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasskeyRegisterRequest {
+    pub name: Option<String>,
+    pub credential: PasskeyCredential,
+}
+
+/// `email` opsional: kalau diisi, `allowCredentials` berisi passkey milik email tersebut (untuk authenticator non-discoverable)
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct PasskeyLoginOptionsRequest {
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasskeyLoginRequest {
+    pub credential: PasskeyCredential,
+}
+
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct PasskeyInfo {
+    pub credential_id: String,
+    pub name: Option<String>,
+    pub algorithm: i32,
+    pub transports: Vec<String>,
+    pub created_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+/// Satu baris audit di tabel `security_events`
+#[derive(Debug, Serialize, ToSchema, Clone)]
+pub struct SecurityEventInfo {
+    pub event_id: i64,
+    pub user_nid: Option<i32>,
+    pub email: Option<String>,
+    pub event_type: String,
+    pub success: bool,
+    pub ip_address: Option<String>,
+    pub device_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    /// ID admin kalau event terjadi saat impersonation
+    pub actor_nid: Option<i32>,
+    pub created_at: Option<String>,
+}
+
+/// Filter audit log, semua opsional. `from` / `to` format `YYYY-MM-DD`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct SecurityEventQuery {
+    pub user_nid: Option<i32>,
+    pub actor_nid: Option<i32>,
+    pub email: Option<String>,
+    pub event_type: Option<String>,
+    pub ip_address: Option<String>,
+    pub success: Option<bool>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct Company {
     pub company_id: String,
@@ -84,6 +236,12 @@ pub struct ResetPasswordRequest {
     pub email: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResendActivationRequest {
+    #[validate(required, email(message = "Invalid email format"))]
+    pub email: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct ChangePasswordRequest {
     #[validate(required, email(message = "Invalid email format"))]
@@ -95,6 +253,99 @@ pub struct ChangePasswordRequest {
     pub reset_password_key: String
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct UpdatePasswordRequest {
+    #[validate(custom(function = "required"))]
+    pub current_password: String,
+
+    #[validate(custom(function = "required"), custom(function = "valid_password"))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangeEmailRequest {
+    #[validate(required, email(message = "Invalid email format"))]
+    pub new_email: Option<String>,
+
+    /// Wajib untuk akun yang punya password
+    #[serde(default)]
+    pub current_password: String,
+
+    /// Kode dari `POST /user/confirmation-code`, untuk akun tanpa password (dibuat lewat login provider)
+    #[serde(default)]
+    pub confirmation_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeleteAccountRequest {
+    /// Wajib untuk akun yang punya password
+    #[serde(default)]
+    pub current_password: String,
+
+    /// Kode dari `POST /user/confirmation-code`, untuk akun tanpa password (dibuat lewat login provider)
+    #[serde(default)]
+    pub confirmation_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct OtpLoginRequest {
+    #[validate(required, email(message = "Invalid email format"))]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct OtpVerifyRequest {
+    #[validate(required, email(message = "Invalid email format"))]
+    pub email: Option<String>,
+
+    #[validate(custom(function = "required"))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TotpCodeRequest {
+    #[validate(custom(function = "required"))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct MfaVerifyRequest {
+    /// Kosong = ambil dari cookie `snakesystem-api-mfa` (login lewat provider eksternal)
+    #[serde(default)]
+    pub mfa_token: String,
+
+    /// Kode 6 digit dari authenticator atau recovery code
+    #[validate(custom(function = "required"))]
+    pub code: String,
+}
+
+/// `?mode=token` di endpoint login: token dikembalikan di body JSON, bukan cookie (mobile / server-to-server)
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuthModeQuery {
+    pub mode: Option<String>,
+}
+
+impl AuthModeQuery {
+    pub fn token_mode(&self) -> bool {
+        self.mode.as_deref() == Some("token")
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: Option<String>,
+}
+
+/// Pasangan token hasil login / refresh
+#[derive(Debug, Clone)]
+pub struct SessionTokens {
+    pub claims: Claims,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Sisa umur refresh token, tidak melewati umur maksimal session
+    pub refresh_ttl: chrono::Duration,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct WebUser {
     pub auth_usernid: i32,
@@ -315,15 +566,22 @@ pub struct SendEmailRequest {
     pub otp_code: i32,
 }
 
-/// Data yang bakal dikembalikan Google saat tuker token
-#[derive(Debug, Deserialize)]
-pub struct GoogleUserInfo {
-    pub id: String,
+/// Profil user dari provider login eksternal (ID token / userinfo) yang sudah dinormalisasi
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    /// Nama provider di registry, mis. `google`, `keycloak`
+    pub provider: String,
+    /// ID user yang stabil di provider (`sub` / `id`)
+    pub subject: String,
     pub email: String,
-    pub verified_email: bool,
+    pub email_verified: bool,
     pub name: Option<String>,
-    pub given_name: Option<String>,
-    pub family_name: Option<String>,
     pub picture: Option<String>,
-    pub locale: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OidcProviderInfo {
+    pub name: String,
+    pub display_name: String,
+    pub login_url: String,
 }
\ No newline at end of file
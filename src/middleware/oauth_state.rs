@@ -0,0 +1,84 @@
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::{REDIS_CLIENT, SECRETS};
+
+/// Data authorization OAuth yang menunggu callback (disimpan per `state`)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingAuthorization {
+    pub pkce_verifier: String,
+    pub client_ip: String,
+    /// Provider yang memulai login, callback dari provider lain ditolak
+    #[serde(default)]
+    pub provider: String,
+    /// Dicocokkan dengan claim `nonce` di ID token
+    #[serde(default)]
+    pub nonce: String,
+}
+
+/// 🗄️ Penyimpanan CSRF state + PKCE verifier di Redis, supaya callback bisa diterima instance mana saja.
+///
+/// - `oauth:state:<state>` berisi [`PendingAuthorization`] dengan TTL `OAUTH_STATE_TTL_SECONDS` (default 600)
+/// - `oauth:ip:<ip>` menghitung authorization yang masih pending per IP, maksimal `OAUTH_STATE_MAX_PER_IP` (default 10)
+pub struct OAuthStateStore;
+
+impl OAuthStateStore {
+    fn ttl_seconds() -> u64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get("OAUTH_STATE_TTL_SECONDS").and_then(|v| v.parse().ok()).unwrap_or(600)
+    }
+
+    fn max_per_ip() -> i64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get("OAUTH_STATE_MAX_PER_IP").and_then(|v| v.parse().ok()).unwrap_or(10)
+    }
+
+    /// Simpan authorization baru. `Ok(false)` kalau IP ini sudah melewati batas pending.
+    pub fn put(state: &str, pending: &PendingAuthorization) -> Result<bool, redis::RedisError> {
+        let mut conn = REDIS_CLIENT
+            .get()
+            .expect("Redis not initialized")
+            .clone();
+
+        let ttl = Self::ttl_seconds();
+        let ip_key = format!("oauth:ip:{}", pending.client_ip);
+
+        let (count,): (i64,) = redis::pipe()
+            .atomic()
+            .incr(&ip_key, 1)
+            .expire(&ip_key, ttl as i64).ignore()
+            .query(&mut conn)?;
+
+        if count > Self::max_per_ip() {
+            conn.decr::<_, _, ()>(&ip_key, 1)?;
+            return Ok(false);
+        }
+
+        let payload = serde_json::to_string(pending).expect("Failed to serialize oauth state");
+        conn.set_ex::<_, _, ()>(format!("oauth:state:{}", state), payload, ttl)?;
+
+        Ok(true)
+    }
+
+    /// Ambil sekaligus hapus (GETDEL) supaya state hanya bisa dipakai sekali
+    pub fn take(state: &str) -> Result<Option<PendingAuthorization>, redis::RedisError> {
+        let mut conn = REDIS_CLIENT
+            .get()
+            .expect("Redis not initialized")
+            .clone();
+
+        let payload: Option<String> = conn.get_del(format!("oauth:state:{}", state))?;
+
+        let pending = payload.and_then(|p| serde_json::from_str::<PendingAuthorization>(&p).ok());
+
+        if let Some(pending) = &pending {
+            let ip_key = format!("oauth:ip:{}", pending.client_ip);
+            let remaining: i64 = conn.decr(&ip_key, 1)?;
+            if remaining <= 0 {
+                conn.del::<_, ()>(&ip_key)?;
+            }
+        }
+
+        Ok(pending)
+    }
+}
@@ -0,0 +1,134 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use rand::RngCore;
+use std::sync::OnceLock;
+
+use crate::{middleware::crypto::encrypt_text, SECRETS};
+
+/// Hasil verifikasi password terhadap nilai yang tersimpan di `users.password`
+#[derive(Debug, PartialEq)]
+pub enum PasswordCheck {
+    /// Password cocok dan hash sudah memakai parameter terbaru
+    Valid,
+    /// Password cocok tapi hash lama (AES-CTR / parameter Argon2 lama), wajib di-hash ulang
+    NeedsRehash,
+    Invalid,
+}
+
+static ARGON2_PARAMS: OnceLock<Params> = OnceLock::new();
+
+/// 🔧 Parameter Argon2id, bisa di-tuning lewat secret `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS`, `ARGON2_PARALLELISM`
+fn load_argon2_params() -> Result<Params, String> {
+    let secrets = SECRETS.get().ok_or("SECRETS not initialized")?;
+    let read = |key: &str, default: u32| -> Result<u32, String> {
+        match secrets.get(key) {
+            Some(value) => value.trim().parse().map_err(|_| format!("{} must be a positive number, got {:?}", key, value)),
+            None => Ok(default),
+        }
+    };
+
+    Params::new(
+        read("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST)?,
+        read("ARGON2_ITERATIONS", Params::DEFAULT_T_COST)?,
+        read("ARGON2_PARALLELISM", Params::DEFAULT_P_COST)?,
+        None,
+    )
+    .map_err(|e| format!("Invalid Argon2 params (ARGON2_MEMORY_KIB / ARGON2_ITERATIONS / ARGON2_PARALLELISM): {}", e))
+}
+
+/// Dipanggil sekali dari `main` setelah `SECRETS` siap, supaya parameter yang salah menggagalkan deploy
+/// (bukan membuat semua login gagal sebagai password salah)
+pub fn init_password_hashing() {
+    let params = load_argon2_params().unwrap_or_else(|e| panic!("{}", e));
+    let _ = ARGON2_PARAMS.set(params);
+    DUMMY_HASH.get_or_init(|| hash_password_blocking("snakesystem-dummy").expect("Failed to hash dummy password"));
+}
+
+fn argon2_params() -> &'static Params {
+    ARGON2_PARAMS.get_or_init(|| load_argon2_params().unwrap_or_else(|e| panic!("{}", e)))
+}
+
+fn argon2() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params().clone())
+}
+
+/// Hash acak untuk verifikasi "palsu" saat user tidak ada / tanpa password, supaya waktu respons sama
+static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+fn hash_password_blocking(plain_text: &str) -> Result<String, argon2::password_hash::Error> {
+    let mut salt_bytes = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt_bytes);
+    let salt = SaltString::encode_b64(&salt_bytes)?;
+
+    Ok(argon2().hash_password(plain_text.as_bytes(), &salt)?.to_string())
+}
+
+/// 🔐 Hash password dengan Argon2id + salt acak per user (format PHC `$argon2id$...`).
+/// Dijalankan di thread blocking supaya worker actix tidak tertahan selama hashing.
+pub async fn hash_password(plain_text: &str) -> Result<String, String> {
+    let plain_text = plain_text.to_string();
+
+    tokio::task::spawn_blocking(move || hash_password_blocking(&plain_text))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+fn verify_password_blocking(plain_text: &str, stored: &str) -> PasswordCheck {
+    if stored.is_empty() {
+        // User tidak ada / akun tanpa password: tetap hitung Argon2 supaya tidak bisa dibedakan dari password salah
+        let dummy = DUMMY_HASH.get_or_init(|| hash_password_blocking("snakesystem-dummy").expect("Failed to hash dummy password"));
+        let _ = verify_password_blocking(plain_text, dummy);
+        return PasswordCheck::Invalid;
+    }
+
+    match PasswordHash::new(stored) {
+        Ok(parsed) => {
+            if argon2().verify_password(plain_text.as_bytes(), &parsed).is_err() {
+                return PasswordCheck::Invalid;
+            }
+
+            // Hash valid tapi parameter beda dengan konfigurasi sekarang -> upgrade
+            let current = argon2_params();
+            let outdated = parsed.algorithm != Algorithm::Argon2id.ident()
+                || Params::try_from(&parsed).map_or(true, |p| {
+                    p.m_cost() != current.m_cost()
+                        || p.t_cost() != current.t_cost()
+                        || p.p_cost() != current.p_cost()
+                });
+
+            if outdated { PasswordCheck::NeedsRehash } else { PasswordCheck::Valid }
+        }
+        // Bukan format PHC -> data lama AES-CTR
+        Err(_) => {
+            if constant_time_eq(encrypt_text(plain_text.to_string()).as_bytes(), stored.as_bytes()) {
+                PasswordCheck::NeedsRehash
+            } else {
+                PasswordCheck::Invalid
+            }
+        }
+    }
+}
+
+/// 🔓 Verifikasi password (di thread blocking), mendukung hash Argon2 dan data lama hasil `encrypt_text`.
+/// `stored` kosong (user tidak ditemukan) tetap memakan waktu yang sama dengan password salah.
+pub async fn verify_password(plain_text: &str, stored: &str) -> PasswordCheck {
+    let (plain_text, stored) = (plain_text.to_string(), stored.to_string());
+
+    tokio::task::spawn_blocking(move || verify_password_blocking(&plain_text, &stored))
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("❌ Password verification error: {}", e);
+            PasswordCheck::Invalid
+        })
+}
+
+/// Bandingkan byte tanpa short-circuit (hindari timing attack)
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
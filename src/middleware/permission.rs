@@ -0,0 +1,122 @@
+use std::{future::{ready, Future, Ready}, pin::Pin, rc::Rc};
+
+use actix_web::{body::EitherBody, dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, Error, HttpMessage, HttpRequest, HttpResponse};
+
+use crate::{middleware::{auth_extractor::{authenticate, AuthenticatedUser}, jwt_session::Claims, model::ApiKeyInfo}, services::api_key_service::{ApiKeyCheck, ApiKeyService}};
+
+/// Pemanggil yang lolos guard: user login atau API key
+#[derive(Debug, Clone)]
+pub enum Principal {
+    User(Claims),
+    ApiKey(ApiKeyInfo),
+}
+
+/// Nilai header `X-Api-Key`, kalau ada
+pub fn api_key(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("X-Api-Key")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Cek API key terhadap scope, `401` kalau key tidak valid, `403` kalau scope kurang
+pub async fn authorize_api_key(api_key: &str, scope: &str) -> Result<ApiKeyInfo, HttpResponse> {
+    match ApiKeyService::verify(api_key, scope).await {
+        Ok(ApiKeyCheck::Valid(info)) => Ok(info),
+        Ok(ApiKeyCheck::Invalid) => Err(HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Invalid or expired API key"
+        }))),
+        Ok(ApiKeyCheck::MissingScope) => Err(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Missing scope: {}", scope)
+        }))),
+        Err(e) => Err(HttpResponse::InternalServerError().json(serde_json::json!({ "error": e }))),
+    }
+}
+
+/// Cek pemanggil terhadap permission.
+/// - API key (`X-Api-Key`): scope = nama permission dengan `.` diganti `:` (mis. `library.write` → `library:write`)
+/// - User: session dari bearer / cookie + permission yang dibawa claims. Permission diisi saat login / refresh,
+///   jadi perubahan role berlaku paling lambat setelah access token diperbarui.
+pub async fn authorize(req: &HttpRequest, permission: &str) -> Result<Principal, HttpResponse> {
+    if let Some(key) = api_key(req) {
+        return authorize_api_key(&key, &permission.replace('.', ":")).await.map(Principal::ApiKey);
+    }
+
+    let AuthenticatedUser { claims, .. } = authenticate(req).await?;
+
+    if !claims.has_permission(permission) {
+        return Err(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Missing permission: {}", permission)
+        })));
+    }
+
+    Ok(Principal::User(claims))
+}
+
+/// 🛡️ Route guard: `#[post("/create", wrap = "RequirePermission::new(\"library.write\")")]`
+/// atau `web::scope("/redis").wrap(RequirePermission::new("redis.admin"))`.
+/// Claims user / [`ApiKeyInfo`] yang lolos disimpan di request extensions.
+pub struct RequirePermission {
+    permission: &'static str,
+}
+
+impl RequirePermission {
+    pub fn new(permission: &'static str) -> Self {
+        Self { permission }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequirePermissionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware {
+            service: Rc::new(service),
+            permission: self.permission,
+        }))
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: Rc<S>,
+    permission: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let permission = self.permission;
+
+        Box::pin(async move {
+            match authorize(req.request(), permission).await {
+                Ok(principal) => {
+                    match principal {
+                        Principal::User(claims) => { req.extensions_mut().insert(claims); }
+                        Principal::ApiKey(info) => { req.extensions_mut().insert(info); }
+                    }
+                    service.call(req).await.map(ServiceResponse::map_into_left_body)
+                }
+                Err(response) => Ok(req.into_response(response).map_into_right_body()),
+            }
+        })
+    }
+}
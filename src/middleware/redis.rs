@@ -2,10 +2,15 @@ use actix_web::{get, delete, put, web, HttpResponse, Responder, Scope};
 use redis::Commands;
 use serde::Deserialize;
 
-use crate::REDIS_CLIENT;
+use crate::{middleware::permission::RequirePermission, REDIS_CLIENT};
 
 pub fn redis_scope() -> Scope {
-    web::scope("/redis").configure(config)
+    web::scope("/redis").service(
+        // scope kosong supaya guard berlaku ke semua route tanpa mengubah tipe return `Scope`
+        web::scope("")
+            .wrap(RequirePermission::new("redis.admin"))
+            .configure(config),
+    )
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -45,7 +50,7 @@ async fn redis_delete_key(key: web::Path<String>) -> impl Responder {
 #[delete("/clear")]
 async fn redis_clear() -> impl Responder {
     match RedisService::clear_all().await {
-        Ok(_) => HttpResponse::Ok().body("All keys deleted"),
+        Ok(deleted) => HttpResponse::Ok().body(format!("{} cache keys deleted", deleted)),
         Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
     }
 }
@@ -67,6 +72,10 @@ async fn redis_update_key(
     }
 }
 
+/// Key cache hasil query (lihat `GenericService::make_cache_key`), satu-satunya yang boleh dihapus massal.
+/// Key keamanan (`jwt:*`, `login:*`, `mfa:*`, `otp:*`, `oauth:*`, `webauthn:*`) tidak boleh ikut terhapus
+const CACHE_KEY_PATTERN: &str = "table:*";
+
 struct RedisService;
 
 impl RedisService {
@@ -103,13 +112,20 @@ impl RedisService {
         conn.del::<_, ()>(key)
     }
 
-    pub async fn clear_all() -> Result<(), redis::RedisError> {
+    /// Hapus semua key cache (SCAN + DEL per 500 key), return jumlah key yang dihapus
+    pub async fn clear_all() -> Result<usize, redis::RedisError> {
+        let keys = Self::list_keys(CACHE_KEY_PATTERN).await?;
+
         let mut conn = REDIS_CLIENT
             .get()
             .expect("Redis not initialized")
             .clone();
 
-        conn.flushdb::<()>()
+        for chunk in keys.chunks(500) {
+            conn.del::<_, ()>(chunk)?;
+        }
+
+        Ok(keys.len())
     }
 
     pub async fn update_key(
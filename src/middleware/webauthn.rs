@@ -0,0 +1,412 @@
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use sha2::{Digest, Sha256};
+
+/// Algoritma COSE yang diterima: ES256, EdDSA, RS256
+pub const COSE_ES256: i64 = -7;
+pub const COSE_EDDSA: i64 = -8;
+pub const COSE_RS256: i64 = -257;
+pub const SUPPORTED_ALGORITHMS: [i64; 3] = [COSE_ES256, COSE_EDDSA, COSE_RS256];
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// Nilai CBOR secukupnya untuk attestation object dan COSE key (tanpa float / tag)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Int(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Bool(bool),
+    Null,
+}
+
+impl Cbor {
+    /// Ambil value dari map berdasarkan key integer (COSE) atau text (attestation object)
+    pub fn get(&self, key: &Cbor) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, key: i128) -> Option<&Cbor> {
+        self.get(&Cbor::Int(key))
+    }
+
+    pub fn get_text(&self, key: &str) -> Option<&Cbor> {
+        self.get(&Cbor::Text(key.to_string()))
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Cbor::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i128> {
+        match self {
+            Cbor::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Decode satu item CBOR, hasilnya item + jumlah byte yang terpakai (authData bisa berisi extension setelah COSE key)
+pub fn decode_cbor(input: &[u8]) -> Result<(Cbor, usize), String> {
+    let mut reader = CborReader { input, position: 0 };
+    let value = reader.read(0)?;
+    Ok((value, reader.position))
+}
+
+struct CborReader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl CborReader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8], String> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.input.len())
+            .ok_or("Unexpected end of CBOR data")?;
+        let slice = &self.input[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn argument(&mut self, info: u8) -> Result<u64, String> {
+        Ok(match info {
+            0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            _ => return Err("Unsupported CBOR length encoding".to_string()),
+        })
+    }
+
+    fn read(&mut self, depth: usize) -> Result<Cbor, String> {
+        if depth > 16 {
+            return Err("CBOR nesting too deep".to_string());
+        }
+
+        let head = self.take(1)?[0];
+        let (major, info) = (head >> 5, head & 0x1f);
+
+        match major {
+            0 => Ok(Cbor::Int(self.argument(info)? as i128)),
+            1 => Ok(Cbor::Int(-1 - self.argument(info)? as i128)),
+            2 => {
+                let length = self.argument(info)? as usize;
+                Ok(Cbor::Bytes(self.take(length)?.to_vec()))
+            }
+            3 => {
+                let length = self.argument(info)? as usize;
+                String::from_utf8(self.take(length)?.to_vec())
+                    .map(Cbor::Text)
+                    .map_err(|_| "Invalid CBOR text".to_string())
+            }
+            4 => {
+                let length = self.argument(info)? as usize;
+                // Setiap item minimal 1 byte, cegah alokasi besar dari length palsu
+                if length > self.input.len() - self.position {
+                    return Err("Unexpected end of CBOR data".to_string());
+                }
+                (0..length).map(|_| self.read(depth + 1)).collect::<Result<_, _>>().map(Cbor::Array)
+            }
+            5 => {
+                let length = self.argument(info)? as usize;
+                if length > self.input.len() - self.position {
+                    return Err("Unexpected end of CBOR data".to_string());
+                }
+                (0..length)
+                    .map(|_| Ok((self.read(depth + 1)?, self.read(depth + 1)?)))
+                    .collect::<Result<_, String>>()
+                    .map(Cbor::Map)
+            }
+            7 => match info {
+                20 => Ok(Cbor::Bool(false)),
+                21 => Ok(Cbor::Bool(true)),
+                22 | 23 => Ok(Cbor::Null),
+                _ => Err("Unsupported CBOR simple value".to_string()),
+            },
+            _ => Err("Unsupported CBOR type".to_string()),
+        }
+    }
+}
+
+/// Isi `authenticatorData`: rpIdHash (32) | flags (1) | signCount (4) | [aaguid (16) | credIdLen (2) | credId | COSE key]
+#[derive(Debug)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: [u8; 32],
+    pub flags: u8,
+    pub sign_count: u32,
+    pub credential_id: Option<Vec<u8>>,
+    /// COSE key apa adanya (bytes CBOR), disimpan ke database tanpa diubah
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl AuthenticatorData {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 37 {
+            return Err("Authenticator data too short".to_string());
+        }
+
+        let mut rp_id_hash = [0u8; 32];
+        rp_id_hash.copy_from_slice(&data[..32]);
+        let flags = data[32];
+        let sign_count = u32::from_be_bytes(data[33..37].try_into().unwrap());
+
+        let (mut credential_id, mut public_key) = (None, None);
+
+        if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+            let rest = &data[37..];
+            if rest.len() < 18 {
+                return Err("Attested credential data too short".to_string());
+            }
+
+            let id_length = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+            let rest = &rest[18..];
+            if rest.len() < id_length {
+                return Err("Attested credential data too short".to_string());
+            }
+
+            let (_, key_length) = decode_cbor(&rest[id_length..])?;
+            credential_id = Some(rest[..id_length].to_vec());
+            public_key = Some(rest[id_length..id_length + key_length].to_vec());
+        }
+
+        Ok(Self { rp_id_hash, flags, sign_count, credential_id, public_key })
+    }
+
+    /// rpIdHash harus SHA-256 dari relying party ID kita
+    pub fn matches_rp_id(&self, rp_id: &str) -> bool {
+        Sha256::digest(rp_id.as_bytes()).as_slice() == self.rp_id_hash
+    }
+
+    pub fn user_present(&self) -> bool {
+        self.flags & FLAG_USER_PRESENT != 0
+    }
+}
+
+/// Algoritma (`3`) dari COSE key, ditolak kalau tidak didukung
+pub fn cose_algorithm(cose_key: &[u8]) -> Result<i64, String> {
+    let (key, _) = decode_cbor(cose_key)?;
+    let algorithm = key.get_int(3).and_then(Cbor::as_int).ok_or("COSE key has no algorithm")? as i64;
+
+    if !SUPPORTED_ALGORITHMS.contains(&algorithm) {
+        return Err(format!("Unsupported passkey algorithm: {}", algorithm));
+    }
+
+    Ok(algorithm)
+}
+
+/// ✍️ Verifikasi signature assertion atas `authenticatorData || SHA-256(clientDataJSON)`
+pub fn verify_signature(cose_key: &[u8], message: &[u8], signature_bytes: &[u8]) -> Result<(), String> {
+    let (key, _) = decode_cbor(cose_key)?;
+    let algorithm = cose_algorithm(cose_key)?;
+    let param = |label: i128| key.get_int(label).and_then(Cbor::as_bytes).ok_or_else(|| "Incomplete COSE key".to_string());
+
+    let verified = match algorithm {
+        COSE_ES256 => {
+            // EC2: x (-2), y (-3) → titik uncompressed 0x04 || x || y
+            let mut point = vec![0x04];
+            point.extend_from_slice(param(-2)?);
+            point.extend_from_slice(param(-3)?);
+            UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_ASN1, point).verify(message, signature_bytes)
+        }
+        COSE_EDDSA => UnparsedPublicKey::new(&signature::ED25519, param(-2)?).verify(message, signature_bytes),
+        COSE_RS256 => RsaPublicKeyComponents { n: param(-1)?, e: param(-2)? }
+            .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature_bytes),
+        _ => unreachable!(),
+    };
+
+    verified.map_err(|_| "Invalid passkey signature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{rand::SystemRandom, signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING}};
+
+    use super::*;
+
+    fn head(major: u8, length: usize) -> Vec<u8> {
+        match length {
+            0..=23 => vec![major << 5 | length as u8],
+            24..=255 => vec![major << 5 | 24, length as u8],
+            _ => [vec![major << 5 | 25], (length as u16).to_be_bytes().to_vec()].concat(),
+        }
+    }
+
+    fn encode(value: &Cbor) -> Vec<u8> {
+        match value {
+            Cbor::Int(n) if *n >= 0 => head(0, *n as usize),
+            Cbor::Int(n) => head(1, (-1 - n) as usize),
+            Cbor::Bytes(bytes) => [head(2, bytes.len()), bytes.clone()].concat(),
+            Cbor::Text(text) => [head(3, text.len()), text.as_bytes().to_vec()].concat(),
+            Cbor::Array(items) => [head(4, items.len()), items.iter().flat_map(encode).collect()].concat(),
+            Cbor::Map(entries) => [
+                head(5, entries.len()),
+                entries.iter().flat_map(|(k, v)| [encode(k), encode(v)].concat()).collect(),
+            ].concat(),
+            Cbor::Bool(false) => vec![0xf4],
+            Cbor::Bool(true) => vec![0xf5],
+            Cbor::Null => vec![0xf6],
+        }
+    }
+
+    fn es256_key() -> (EcdsaKeyPair, Vec<u8>) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        let point = pair.public_key().as_ref();
+        let cose = encode(&Cbor::Map(vec![
+            (Cbor::Int(1), Cbor::Int(2)),
+            (Cbor::Int(3), Cbor::Int(COSE_ES256 as i128)),
+            (Cbor::Int(-1), Cbor::Int(1)),
+            (Cbor::Int(-2), Cbor::Bytes(point[1..33].to_vec())),
+            (Cbor::Int(-3), Cbor::Bytes(point[33..].to_vec())),
+        ]));
+        (pair, cose)
+    }
+
+    fn ed25519_key() -> (Ed25519KeyPair, Vec<u8>) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let cose = encode(&Cbor::Map(vec![
+            (Cbor::Int(1), Cbor::Int(1)),
+            (Cbor::Int(3), Cbor::Int(COSE_EDDSA as i128)),
+            (Cbor::Int(-1), Cbor::Int(6)),
+            (Cbor::Int(-2), Cbor::Bytes(pair.public_key().as_ref().to_vec())),
+        ]));
+        (pair, cose)
+    }
+
+    fn auth_data(flags: u8, sign_count: u32, attested: Option<(&[u8], &[u8])>) -> Vec<u8> {
+        let mut data = Sha256::digest(b"example.com").to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        if let Some((credential_id, cose_key)) = attested {
+            data.extend_from_slice(&[0u8; 16]);
+            data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+            data.extend_from_slice(credential_id);
+            data.extend_from_slice(cose_key);
+        }
+        data
+    }
+
+    fn assertion_message(auth_data: &[u8]) -> Vec<u8> {
+        [auth_data, Sha256::digest(br#"{"type":"webauthn.get"}"#).as_slice()].concat()
+    }
+
+    #[test]
+    fn decode_cbor_returns_item_and_consumed_length() {
+        let value = Cbor::Map(vec![
+            (Cbor::Text("fmt".to_string()), Cbor::Text("none".to_string())),
+            (Cbor::Int(-7), Cbor::Array(vec![Cbor::Bool(true), Cbor::Null, Cbor::Bytes(vec![1, 2, 3])])),
+        ]);
+        let mut bytes = encode(&value);
+        let length = bytes.len();
+        bytes.extend_from_slice(&[0xa0, 0xff]); // extension setelah item pertama
+
+        let (decoded, used) = decode_cbor(&bytes).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(used, length);
+        assert_eq!(decoded.get_text("fmt"), Some(&Cbor::Text("none".to_string())));
+    }
+
+    #[test]
+    fn decode_cbor_rejects_oversized_lengths() {
+        let oversized = [
+            vec![0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], // bytes, panjang u64::MAX
+            vec![0x7a, 0xff, 0xff, 0xff, 0xff, b'a'],                   // text, panjang u32::MAX
+            vec![0x9a, 0xff, 0xff, 0xff, 0xff, 0x01],                   // array, u32::MAX item
+            vec![0xbb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], // map, u64::MAX entry
+            vec![0x45, 0x01, 0x02],                                     // bytes 5, isi hanya 2
+        ];
+
+        for input in oversized {
+            assert!(decode_cbor(&input).is_err(), "accepted {:02x?}", input);
+        }
+    }
+
+    #[test]
+    fn decode_cbor_rejects_deep_nesting_and_unsupported_types() {
+        let mut nested = vec![0x81; 32];
+        nested.push(0x00);
+        assert!(decode_cbor(&nested).is_err());
+
+        assert!(decode_cbor(&[0xc0, 0x00]).is_err()); // tag
+        assert!(decode_cbor(&[0xfb, 0, 0, 0, 0, 0, 0, 0, 0]).is_err()); // float
+        assert!(decode_cbor(&[0x1c]).is_err()); // length encoding 28
+        assert!(decode_cbor(&[]).is_err());
+    }
+
+    #[test]
+    fn parse_reads_header_and_attested_credential() {
+        let (_, cose) = ed25519_key();
+        let mut data = auth_data(FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL, 7, Some((b"cred-1", &cose)));
+        data.extend_from_slice(&[0xa0]); // extension map kosong
+
+        let parsed = AuthenticatorData::parse(&data).unwrap();
+        assert!(parsed.matches_rp_id("example.com"));
+        assert!(!parsed.matches_rp_id("evil.example"));
+        assert!(parsed.user_present());
+        assert_eq!(parsed.sign_count, 7);
+        assert_eq!(parsed.credential_id.as_deref(), Some(&b"cred-1"[..]));
+        assert_eq!(parsed.public_key.as_deref(), Some(&cose[..]));
+        assert_eq!(cose_algorithm(&cose), Ok(COSE_EDDSA));
+    }
+
+    #[test]
+    fn parse_rejects_truncated_auth_data() {
+        let (_, cose) = es256_key();
+        let full = auth_data(FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL, 1, Some((b"cred-1", &cose)));
+
+        assert!(AuthenticatorData::parse(&full[..36]).is_err());
+        assert!(AuthenticatorData::parse(&full[..37 + 17]).is_err()); // aaguid / credIdLen terpotong
+        assert!(AuthenticatorData::parse(&full[..37 + 18 + 3]).is_err()); // credential ID terpotong
+        assert!(AuthenticatorData::parse(&full[..full.len() - 1]).is_err()); // COSE key terpotong
+
+        let plain = AuthenticatorData::parse(&auth_data(0, 0, None)).unwrap();
+        assert!(!plain.user_present());
+        assert!(plain.credential_id.is_none());
+    }
+
+    #[test]
+    fn verify_signature_accepts_es256_assertion() {
+        let (pair, cose) = es256_key();
+        let message = assertion_message(&auth_data(FLAG_USER_PRESENT, 1, None));
+        let signature = pair.sign(&SystemRandom::new(), &message).unwrap();
+
+        assert_eq!(verify_signature(&cose, &message, signature.as_ref()), Ok(()));
+
+        let mut tampered = message.clone();
+        tampered[33] ^= 0x01;
+        assert!(verify_signature(&cose, &tampered, signature.as_ref()).is_err());
+    }
+
+    #[test]
+    fn verify_signature_accepts_ed25519_assertion() {
+        let (pair, cose) = ed25519_key();
+        let (_, other_cose) = ed25519_key();
+        let message = assertion_message(&auth_data(FLAG_USER_PRESENT, 0, None));
+        let signature = pair.sign(&message);
+
+        assert_eq!(verify_signature(&cose, &message, signature.as_ref()), Ok(()));
+        assert!(verify_signature(&other_cose, &message, signature.as_ref()).is_err());
+        assert!(verify_signature(&cose, &message, &signature.as_ref()[..63]).is_err());
+    }
+
+    #[test]
+    fn verify_signature_rejects_unsupported_or_incomplete_keys() {
+        let message = assertion_message(&auth_data(FLAG_USER_PRESENT, 1, None));
+
+        let unsupported = encode(&Cbor::Map(vec![(Cbor::Int(3), Cbor::Int(-36))]));
+        assert_eq!(verify_signature(&unsupported, &message, &[0; 64]), Err("Unsupported passkey algorithm: -36".to_string()));
+
+        let incomplete = encode(&Cbor::Map(vec![(Cbor::Int(3), Cbor::Int(COSE_ES256 as i128))]));
+        assert_eq!(verify_signature(&incomplete, &message, &[0; 64]), Err("Incomplete COSE key".to_string()));
+    }
+}
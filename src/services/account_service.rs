@@ -0,0 +1,597 @@
+use std::collections::HashMap;
+
+use actix_web::HttpRequest;
+use sqlx::Row;
+
+use crate::{middleware::{crypto::hash_token, jwt_session::Claims, model::{ActionResult, ChangeEmailRequest, DeleteAccountRequest, UpdatePasswordRequest}, password::{hash_password, verify_password, PasswordCheck}}, services::{api_key_service::ApiKeyService, audit_service::{AuditService, SecurityEvent, SecurityEventType}, generic_service::GenericService, mail_service::MailService, otp_service::OtpService}, CONNECTION, SECRETS};
+
+/// 👤 Pengaturan akun oleh user yang sedang login (ganti password, ganti email)
+pub struct AccountService;
+
+impl AccountService {
+
+    async fn notify(template: &str, email: String, username: Option<String>, subject: &str, extra: Vec<(&str, String)>) {
+        let mut mail_data = HashMap::new();
+        mail_data.insert("username".to_string(), username);
+        mail_data.insert("company_name".to_string(), Some("PT. TECH SNAKE SYSTEM".to_string()));
+        mail_data.insert("subject".to_string(), Some(subject.to_string()));
+        mail_data.insert("email".to_string(), Some(email));
+        mail_data.insert("title".to_string(), Some("KEAMANAN AKUN CUSTOMER ONBOARDING".to_string()));
+        mail_data.insert("changed_at".to_string(), Some(GenericService::get_timestamp().format("%d-%m-%Y %H:%M:%S WIB").to_string()));
+        for (key, value) in extra {
+            mail_data.insert(key.to_string(), Some(value));
+        }
+
+        let mail_result: ActionResult<String, String> = MailService::send(mail_data, template).await;
+
+        if let Some(e) = mail_result.error {
+            println!("❌ Mail Error: {}", e);
+        }
+    }
+
+    /// Hash password (kosong untuk akun dari login provider) + data kontak user
+    async fn load_account(usernid: i32) -> Result<Option<(String, String, Option<String>)>, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let row = sqlx::query(r#"SELECT A.password, A.email, B.fullname FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE A.web_cif_id = $1"#)
+            .bind(usernid)
+            .fetch_optional(connection)
+            .await
+            .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+        let Some(row) = row else { return Ok(None) };
+        let stored: String = row.try_get::<Option<String>, _>("password").unwrap_or_default().unwrap_or_default();
+
+        Ok(Some((stored, row.get("email"), row.get("fullname"))))
+    }
+
+    /// Ambil hash password + data kontak user, `Ok(None)` kalau password salah
+    async fn check_current_password(usernid: i32, current_password: &str) -> Result<Option<(String, String, Option<String>)>, String> {
+        let Some((stored, email, fullname)) = Self::load_account(usernid).await? else { return Ok(None) };
+
+        if verify_password(current_password, &stored).await == PasswordCheck::Invalid {
+            return Ok(None);
+        }
+
+        Ok(Some((stored, email, fullname)))
+    }
+
+    /// Konfirmasi aksi sensitif (hapus akun, ganti email): password saat ini, atau kode email
+    /// dari [`OtpService::request_confirmation`] untuk akun tanpa password. `Ok(Err(pesan))` kalau gagal.
+    async fn confirm_identity(usernid: i32, current_password: &str, confirmation_code: Option<&str>) -> Result<Result<(String, Option<String>), String>, String> {
+        let Some((stored, email, fullname)) = Self::load_account(usernid).await? else {
+            return Ok(Err("User not found".to_string()));
+        };
+
+        if !stored.is_empty() {
+            if verify_password(current_password, &stored).await == PasswordCheck::Invalid {
+                return Ok(Err("Current password is incorrect".to_string()));
+            }
+            return Ok(Ok((email, fullname)));
+        }
+
+        let Some(code) = confirmation_code.filter(|code| !code.trim().is_empty()) else {
+            return Ok(Err("Confirmation code is required, request one through /user/confirmation-code".to_string()));
+        };
+
+        match OtpService::verify_confirmation(usernid, code) {
+            Ok(true) => Ok(Ok((email, fullname))),
+            Ok(false) => Ok(Err("Invalid or expired confirmation code".to_string())),
+            Err(e) => Err(format!("Redis error: {}", e)),
+        }
+    }
+
+    /// Ganti password dengan password lama, session di device lain ikut dicabut
+    pub async fn change_password(session: Claims, current_token: &str, request: UpdatePasswordRequest, req: &HttpRequest) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let (stored, email, fullname) = match Self::check_current_password(session.usernid, &request.current_password).await {
+            Ok(Some(found)) => found,
+            Ok(None) => {
+                result.message = "Current password is incorrect".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        if request.current_password == request.new_password {
+            result.message = "New password must be different from the current password".to_string();
+            return result;
+        }
+
+        let enc_password = match hash_password(&request.new_password).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                result.error = Some(format!("Failed to hash password: {}", e));
+                return result;
+            }
+        };
+
+        let mut trans = match connection.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                result.error = Some(format!("Database error: {}", e));
+                return result;
+            }
+        };
+
+        // Guard password lama supaya dua request paralel tidak saling timpa
+        match sqlx::query(r#"UPDATE users SET password = $1 WHERE web_cif_id = $2 AND password = $3"#)
+            .bind(&enc_password)
+            .bind(session.usernid)
+            .bind(&stored)
+            .execute(&mut *trans)
+            .await {
+                Ok(row) if row.rows_affected() == 1 => {}
+                Ok(_) => {
+                    result.message = "Password was changed by another request, please try again".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to update users: {}", e));
+                    return result;
+                }
+            }
+
+        if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1 AND token_cookie <> $2"#)
+            .bind(session.usernid)
+            .bind(current_token)
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to delete cookies: {}", e));
+                return result;
+            }
+
+        if let Err(e) = trans.commit().await {
+            result.error = Some(format!("Failed to commit transaction: {}", e));
+            return result;
+        }
+
+        AuditService::record(SecurityEvent {
+            user_nid: Some(session.usernid),
+            email: Some(email.clone()),
+            detail: Some("current_password".to_string()),
+            ..SecurityEvent::from_request(SecurityEventType::PasswordChanged, req)
+        }).await;
+
+        Self::notify("password-changed", email, fullname, "Password Anda Telah Diubah", Vec::new()).await;
+
+        result.result = true;
+        result.message = "Password changed successfully".to_string();
+        result
+    }
+
+    /// Mulai ganti email: link konfirmasi dikirim ke email baru, email lama diberi pemberitahuan.
+    /// Link berlaku `EMAIL_CHANGE_HOURS` (default 24).
+    pub async fn request_email_change(session: Claims, request: ChangeEmailRequest) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let front_url = secrets.get("FRONT_URL").expect("secret was not found");
+        let expires_hours: i64 = secrets.get("EMAIL_CHANGE_HOURS").and_then(|v| v.parse().ok()).unwrap_or(24);
+
+        let new_email = request.new_email.unwrap_or_default().trim().to_lowercase();
+
+        let (email, fullname) = match Self::confirm_identity(session.usernid, &request.current_password, request.confirmation_code.as_deref()).await {
+            Ok(Ok(found)) => found,
+            Ok(Err(message)) => {
+                result.message = message;
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        if new_email == email.to_lowercase() {
+            result.message = "New email must be different from the current email".to_string();
+            return result;
+        }
+
+        match sqlx::query(r#"SELECT 1 FROM users WHERE lower(email) = $1"#)
+            .bind(&new_email)
+            .fetch_optional(connection)
+            .await {
+                Ok(Some(_)) => {
+                    result.message = "Email already exists".to_string();
+                    return result;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            }
+
+        let change_key = GenericService::random_string(64);
+
+        if let Err(e) = sqlx::query(r#"UPDATE users
+            SET pending_email = $1, email_change_key = $2, email_change_date = $3
+            WHERE web_cif_id = $4"#)
+            .bind(&new_email)
+            .bind(hash_token(&change_key))
+            .bind(GenericService::get_timestamp())
+            .bind(session.usernid)
+            .execute(connection)
+            .await {
+                result.error = Some(format!("Failed to update users: {}", e));
+                return result;
+            }
+
+        Self::notify("email-change-confirm", new_email.clone(), fullname.clone(), "Konfirmasi Email Baru Anda", vec![
+            ("front_url", format!("{}/confirm-email/{}", front_url, change_key)),
+            ("expires_hours", expires_hours.to_string()),
+        ]).await;
+
+        Self::notify("email-change-notice", email, fullname, "Permintaan Ganti Email Akun", vec![
+            ("new_email", new_email),
+            ("notice", "Ada permintaan untuk mengganti email akun Anda. Perubahan baru berlaku setelah dikonfirmasi dari email baru.".to_string()),
+        ]).await;
+
+        result.result = true;
+        result.message = "Confirmation link has been sent to the new email".to_string();
+        result
+    }
+
+    /// Konfirmasi ganti email dari link, semua session dicabut karena email di JWT sudah tidak berlaku
+    pub async fn confirm_email_change(change_key: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let expires_hours: i64 = secrets.get("EMAIL_CHANGE_HOURS").and_then(|v| v.parse().ok()).unwrap_or(24);
+
+        let mut trans = match connection.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                result.error = Some(format!("Database error: {}", e));
+                return result;
+            }
+        };
+
+        let row = match sqlx::query(r#"SELECT A.web_cif_id, A.email, A.pending_email, B.fullname FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE A.email_change_key = $1 AND A.email_change_date > $2 AND A.pending_email IS NOT NULL
+            FOR UPDATE OF A"#)
+            .bind(hash_token(change_key))
+            .bind(GenericService::get_timestamp() - chrono::Duration::hours(expires_hours))
+            .fetch_optional(&mut *trans)
+            .await {
+                Ok(Some(row)) => row,
+                Ok(None) => {
+                    result.message = "Email change link is invalid or has expired".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            };
+
+        let usernid: i32 = row.get("web_cif_id");
+        let old_email: String = row.get("email");
+        let new_email: String = row.get("pending_email");
+        let fullname: Option<String> = row.get("fullname");
+
+        // Email bisa saja sudah dipakai akun lain sejak link dikirim
+        match sqlx::query(r#"SELECT 1 FROM users WHERE lower(email) = $1 AND web_cif_id <> $2"#)
+            .bind(&new_email)
+            .bind(usernid)
+            .fetch_optional(&mut *trans)
+            .await {
+                Ok(Some(_)) => {
+                    result.message = "Email already exists".to_string();
+                    return result;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            }
+
+        if let Err(e) = sqlx::query(r#"UPDATE users
+            SET email = $1, pending_email = NULL, email_change_key = NULL, email_change_date = NULL
+            WHERE web_cif_id = $2"#)
+            .bind(&new_email)
+            .bind(usernid)
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to update users: {}", e));
+                return result;
+            }
+
+        if let Err(e) = sqlx::query(r#"UPDATE user_kyc SET email = $1, last_update = $2 WHERE autonid = $3"#)
+            .bind(&new_email)
+            .bind(GenericService::get_timestamp())
+            .bind(usernid)
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to update user_kyc: {}", e));
+                return result;
+            }
+
+        if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1"#)
+            .bind(usernid)
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to delete cookies: {}", e));
+                return result;
+            }
+
+        if let Err(e) = trans.commit().await {
+            result.error = Some(format!("Failed to commit transaction: {}", e));
+            return result;
+        }
+
+        Self::notify("email-change-notice", old_email, fullname, "Email Akun Anda Telah Diganti", vec![
+            ("new_email", new_email),
+            ("notice", "Email akun Anda telah diganti. Email ini tidak lagi bisa dipakai untuk login.".to_string()),
+        ]).await;
+
+        result.result = true;
+        result.message = "Email changed successfully, please login again".to_string();
+        result
+    }
+
+    fn deletion_grace_days() -> i64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get("ACCOUNT_DELETION_GRACE_DAYS").and_then(|v| v.parse().ok()).unwrap_or(30)
+    }
+
+    /// Minta hapus akun, link konfirmasi berlaku `ACCOUNT_DELETION_LINK_HOURS` (default 24)
+    pub async fn request_deletion(session: Claims, request: DeleteAccountRequest) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let front_url = secrets.get("FRONT_URL").expect("secret was not found");
+        let expires_hours: i64 = secrets.get("ACCOUNT_DELETION_LINK_HOURS").and_then(|v| v.parse().ok()).unwrap_or(24);
+
+        let (email, fullname) = match Self::confirm_identity(session.usernid, &request.current_password, request.confirmation_code.as_deref()).await {
+            Ok(Ok(found)) => found,
+            Ok(Err(message)) => {
+                result.message = message;
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        let deletion_key = GenericService::random_string(64);
+
+        match sqlx::query(r#"UPDATE users SET deletion_key = $1, deletion_key_date = $2
+            WHERE web_cif_id = $3 AND deletion_scheduled_at IS NULL"#)
+            .bind(hash_token(&deletion_key))
+            .bind(GenericService::get_timestamp())
+            .bind(session.usernid)
+            .execute(connection)
+            .await {
+                Ok(row) if row.rows_affected() == 1 => {}
+                Ok(_) => {
+                    result.message = "Account deletion is already scheduled".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to update users: {}", e));
+                    return result;
+                }
+            }
+
+        Self::notify("account-deletion-confirm", email, fullname, "Konfirmasi Penghapusan Akun", vec![
+            ("front_url", format!("{}/confirm-delete/{}", front_url, deletion_key)),
+            ("expires_hours", expires_hours.to_string()),
+            ("grace_days", Self::deletion_grace_days().to_string()),
+        ]).await;
+
+        result.result = true;
+        result.message = "Confirmation link has been sent to your email".to_string();
+        result
+    }
+
+    /// Konfirmasi dari link: akun dijadwalkan dihapus setelah masa tenggang, semua session di-logout.
+    /// Selama masa tenggang user masih bisa login dan membatalkan.
+    pub async fn confirm_deletion(deletion_key: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let expires_hours: i64 = secrets.get("ACCOUNT_DELETION_LINK_HOURS").and_then(|v| v.parse().ok()).unwrap_or(24);
+
+        let now = GenericService::get_timestamp();
+        let scheduled_at = now + chrono::Duration::days(Self::deletion_grace_days());
+
+        let mut trans = match connection.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                result.error = Some(format!("Database error: {}", e));
+                return result;
+            }
+        };
+
+        let row = match sqlx::query(r#"UPDATE users A
+            SET deletion_scheduled_at = $1, deletion_key = NULL, deletion_key_date = NULL
+            FROM user_kyc B
+            WHERE A.web_cif_id = B.autonid AND A.deletion_key = $2 AND A.deletion_key_date > $3 AND A.deleted_at IS NULL
+            RETURNING A.web_cif_id, A.email, B.fullname"#)
+            .bind(scheduled_at)
+            .bind(hash_token(deletion_key))
+            .bind(now - chrono::Duration::hours(expires_hours))
+            .fetch_optional(&mut *trans)
+            .await {
+                Ok(Some(row)) => row,
+                Ok(None) => {
+                    result.message = "Deletion link is invalid or has expired".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to update users: {}", e));
+                    return result;
+                }
+            };
+
+        let usernid: i32 = row.get("web_cif_id");
+
+        if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1"#)
+            .bind(usernid)
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to delete cookies: {}", e));
+                return result;
+            }
+
+        if let Err(e) = trans.commit().await {
+            result.error = Some(format!("Failed to commit transaction: {}", e));
+            return result;
+        }
+
+        Self::notify("account-deletion-scheduled", row.get("email"), row.get("fullname"), "Akun Anda Dijadwalkan Untuk Dihapus", vec![
+            ("notice", "Permintaan penghapusan akun Anda telah dikonfirmasi.".to_string()),
+            ("deletion_date", scheduled_at.format("%d-%m-%Y %H:%M WIB").to_string()),
+        ]).await;
+
+        result.result = true;
+        result.message = format!("Account will be deleted on {}", scheduled_at.format("%Y-%m-%d %H:%M:%S"));
+        result
+    }
+
+    pub async fn cancel_deletion(session: Claims) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"UPDATE users SET deletion_scheduled_at = NULL, deletion_key = NULL, deletion_key_date = NULL
+            WHERE web_cif_id = $1 AND deleted_at IS NULL AND (deletion_scheduled_at IS NOT NULL OR deletion_key IS NOT NULL)"#)
+            .bind(session.usernid)
+            .execute(connection)
+            .await {
+                Ok(row) if row.rows_affected() > 0 => {
+                    result.result = true;
+                    result.message = "Account deletion cancelled".to_string();
+                }
+                Ok(_) => {
+                    result.message = "No account deletion is pending".to_string();
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to update users: {}", e));
+                }
+            }
+
+        result
+    }
+
+    /// 🧹 Anonimkan akun yang masa tenggangnya sudah lewat. Dipanggil berkala oleh [`JobService`](crate::services::job_service::JobService).
+    /// Data pribadi dihapus, tapi baris + nomor CIF / client / tanggal tetap ada untuk kebutuhan audit & regulasi.
+    pub async fn purge_deleted_accounts() -> Result<u64, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let now = GenericService::get_timestamp();
+        let mut purged = 0;
+
+        let due: Vec<i32> = sqlx::query_scalar(r#"SELECT web_cif_id FROM users
+            WHERE deletion_scheduled_at <= $1 AND deleted_at IS NULL"#)
+            .bind(now)
+            .fetch_all(connection)
+            .await
+            .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+        for usernid in due {
+            let mut trans = connection.begin().await.map_err(|e| format!("Database error: {}", e))?;
+            let placeholder = format!("deleted-{}@deleted.invalid", usernid);
+
+            // SKIP LOCKED supaya instance lain yang menjalankan job yang sama tidak dobel
+            let locked = sqlx::query(r#"SELECT 1 FROM users
+                WHERE web_cif_id = $1 AND deletion_scheduled_at <= $2 AND deleted_at IS NULL
+                FOR UPDATE SKIP LOCKED"#)
+                .bind(usernid)
+                .bind(now)
+                .fetch_optional(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+            if locked.is_none() {
+                continue;
+            }
+
+            sqlx::query(r#"UPDATE users SET
+                    email = $2, handphone = '', password = '', picture = '', google_id = '',
+                    activate_code = md5(random()::TEXT), otp_generated_link = md5(random()::TEXT), reset_password_key = NULL, reset_password_flag = false,
+                    totp_secret = NULL, totp_enabled = false, totp_enabled_at = NULL, totp_recovery_codes = '{}',
+                    pending_email = NULL, email_change_key = NULL, email_change_date = NULL,
+                    deletion_key = NULL, deletion_key_date = NULL,
+                    disable_login = true, deleted_at = $3
+                WHERE web_cif_id = $1"#)
+                .bind(usernid)
+                .bind(&placeholder)
+                .bind(now)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to update users: {}", e))?;
+
+            sqlx::query(r#"UPDATE user_kyc SET
+                    email = $2, fullname = 'Deleted User', mobile_phone = '', save_ip_address = NULL,
+                    mother_name = NULL, birth_date = NULL, birth_place = NULL,
+                    idcard_number = NULL, idcard_expire_date = NULL,
+                    idcard_city = NULL, idcard_district = NULL, idcard_subdistrict = NULL, idcard_rw = NULL, idcard_rt = NULL,
+                    idcard_address = NULL, idcard_zipcode = NULL,
+                    domicile_city = NULL, domicile_district = NULL, domicile_subdistrict = NULL, domicile_rw = NULL, domicile_rt = NULL,
+                    domicile_address = NULL, domicile_zipcode = NULL,
+                    bank_account_number = NULL, bank_account_holder = NULL,
+                    npwp_number = NULL, company_name = NULL, company_address = NULL,
+                    spouse_relationship = NULL, spouse_relationship_text = NULL, spouse_name = NULL,
+                    spouse_occupation = NULL, spouse_occupation_text = NULL, spouse_nature_of_business = NULL,
+                    spouse_company_name = NULL, spouse_company_address = NULL, spouse_company_city = NULL, spouse_company_zipcode = NULL,
+                    spouse_fund_source = NULL, spouse_fund_source_text = NULL,
+                    idcard_file = NULL, selfie_file = NULL, signature_file = NULL, npwp_file = NULL,
+                    last_update = $3
+                WHERE autonid = $1"#)
+                .bind(usernid)
+                .bind(&placeholder)
+                .bind(now)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to update user_kyc: {}", e))?;
+
+            sqlx::query(r#"UPDATE user_request SET referal = NULL WHERE web_cif_nid = $1"#)
+                .bind(usernid)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to update user_request: {}", e))?;
+
+            sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1"#)
+                .bind(usernid)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to delete cookies: {}", e))?;
+
+            sqlx::query(r#"DELETE FROM user_roles WHERE user_nid = $1"#)
+                .bind(usernid)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to delete user_roles: {}", e))?;
+
+            sqlx::query(r#"DELETE FROM webauthn_credentials WHERE user_nid = $1"#)
+                .bind(usernid)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to delete webauthn_credentials: {}", e))?;
+
+            sqlx::query(r#"DELETE FROM user_identities WHERE user_nid = $1"#)
+                .bind(usernid)
+                .execute(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to delete user_identities: {}", e))?;
+
+            ApiKeyService::revoke_owned_by(&mut trans, usernid).await?;
+
+            trans.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+            purged += 1;
+        }
+
+        Ok(purged)
+    }
+}
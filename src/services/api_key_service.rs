@@ -0,0 +1,264 @@
+use sqlx::{postgres::PgRow, Row};
+
+use crate::{middleware::{crypto::hash_token, model::{ActionResult, ApiKeyInfo, CreateApiKeyRequest}, permission::Principal}, services::generic_service::GenericService, CONNECTION};
+
+/// Prefix supaya API key gampang dikenali (mis. oleh secret scanner)
+const KEY_PREFIX: &str = "ssk_";
+
+/// Hasil pengecekan `X-Api-Key` terhadap scope yang dibutuhkan endpoint
+#[derive(Debug)]
+pub enum ApiKeyCheck {
+    Valid(ApiKeyInfo),
+    /// Key tidak dikenal, sudah dicabut, atau expired
+    Invalid,
+    /// Key valid tapi tidak punya scope yang dibutuhkan
+    MissingScope,
+}
+
+/// 🔑 API key untuk client mesin. Scope dipisah `:`, `*` berlaku untuk semua segmen setelahnya:
+/// `data:read:notes`, `data:read:*`, `options:read:city`, `library:write`, `redis:admin`.
+pub struct ApiKeyService;
+
+impl ApiKeyService {
+
+    pub fn scope_allows(granted: &str, required: &str) -> bool {
+        let mut granted = granted.split(':');
+        let mut required = required.split(':');
+
+        loop {
+            match (granted.next(), required.next()) {
+                (Some("*"), _) => return true,
+                (Some(g), Some(r)) if g == r => continue,
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Scope yang boleh dibagikan pembuat key: scope key-nya sendiri, atau permission user (`.` → `:`).
+    fn grantable_scopes(creator: &Principal) -> Vec<String> {
+        match creator {
+            Principal::ApiKey(info) => info.scopes.clone(),
+            Principal::User(claims) => Self::user_scopes(&claims.permissions),
+        }
+    }
+
+    /// Scope dari permission user: `data.export` mencakup `data:read:*`, semua user login boleh `options:read:*`
+    fn user_scopes(permissions: &[String]) -> Vec<String> {
+        let mut scopes: Vec<String> = permissions.iter().map(|p| p.replace('.', ":")).collect();
+        if permissions.iter().any(|p| p == "data.export") {
+            scopes.push("data:read:*".to_string());
+        }
+        scopes.push("options:read:*".to_string());
+        scopes
+    }
+
+    fn info_from_row(row: &PgRow) -> ApiKeyInfo {
+        ApiKeyInfo {
+            api_key_id: row.get("api_key_id"),
+            created_by: row.get("created_by"),
+            name: row.get("name"),
+            key_prefix: row.get("key_prefix"),
+            scopes: row.get("scopes"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            last_used_at: row.get("last_used_at"),
+            revoked_at: row.get("revoked_at"),
+        }
+    }
+
+    /// Buat key baru, nilai asli hanya dikembalikan sekali (yang disimpan hanya hash).
+    /// Scope tidak boleh melebihi akses pembuatnya, supaya `api_keys.manage` tidak bisa dipakai untuk eskalasi.
+    pub async fn create(request: CreateApiKeyRequest, creator: &Principal) -> ActionResult<serde_json::Value, String> {
+        let mut result: ActionResult<serde_json::Value, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let scopes: Vec<String> = request.scopes.iter()
+            .map(|scope| scope.trim().to_lowercase())
+            .filter(|scope| !scope.is_empty())
+            .collect();
+
+        if scopes.is_empty() {
+            result.message = "At least one scope is required".to_string();
+            return result;
+        }
+
+        let grantable = Self::grantable_scopes(creator);
+        let not_allowed: Vec<&str> = scopes.iter()
+            .filter(|scope| !grantable.iter().any(|granted| Self::scope_allows(granted, scope)))
+            .map(String::as_str)
+            .collect();
+
+        if !not_allowed.is_empty() {
+            result.message = format!("Scope not allowed: {}", not_allowed.join(", "));
+            return result;
+        }
+
+        // Key turunan ikut pemilik key pembuatnya, supaya aksesnya tetap dicek terhadap user yang sama
+        let created_by = match creator {
+            Principal::User(claims) => Some(claims.usernid),
+            Principal::ApiKey(info) => info.created_by,
+        };
+
+        let now = GenericService::get_timestamp();
+        let expires_at = match request.expires_in_days.filter(|days| *days > 0) {
+            Some(days) => match chrono::TimeDelta::try_days(days).and_then(|ttl| now.checked_add_signed(ttl)) {
+                Some(expires_at) => Some(expires_at),
+                None => {
+                    result.message = "expires_in_days is out of range".to_string();
+                    return result;
+                }
+            },
+            None => None,
+        };
+
+        let api_key = format!("{}{}", KEY_PREFIX, GenericService::random_string(40));
+        let key_prefix: String = api_key.chars().take(KEY_PREFIX.len() + 6).collect();
+
+        match sqlx::query(r#"INSERT INTO api_keys (name, key_prefix, key_hash, scopes, created_by, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING api_key_id"#)
+            .bind(request.name.trim())
+            .bind(&key_prefix)
+            .bind(hash_token(&api_key))
+            .bind(&scopes)
+            .bind(created_by)
+            .bind(now)
+            .bind(expires_at)
+            .fetch_one(connection)
+            .await {
+                Ok(row) => {
+                    result.result = true;
+                    result.message = "API key created, store it now because it will not be shown again".to_string();
+                    result.data = Some(serde_json::json!({
+                        "api_key_id": row.get::<i32, _>("api_key_id"),
+                        "api_key": api_key,
+                        "scopes": scopes,
+                        "expires_at": expires_at.map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    }));
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to insert api_keys: {}", e));
+                }
+            }
+
+        result
+    }
+
+    pub async fn list() -> ActionResult<Vec<ApiKeyInfo>, String> {
+        let mut result: ActionResult<Vec<ApiKeyInfo>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"SELECT api_key_id, created_by, name, key_prefix, scopes,
+                to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at,
+                to_char(expires_at, 'YYYY-MM-DD HH24:MI:SS') AS expires_at,
+                to_char(last_used_at, 'YYYY-MM-DD HH24:MI:SS') AS last_used_at,
+                to_char(revoked_at, 'YYYY-MM-DD HH24:MI:SS') AS revoked_at
+            FROM api_keys
+            ORDER BY api_key_id DESC"#)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => {
+                    result.result = true;
+                    result.data = Some(rows.iter().map(Self::info_from_row).collect());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch api_keys: {}", e));
+                }
+            }
+
+        result
+    }
+
+    pub async fn revoke(api_key_id: i32) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"UPDATE api_keys SET revoked_at = $2 WHERE api_key_id = $1 AND revoked_at IS NULL"#)
+            .bind(api_key_id)
+            .bind(GenericService::get_timestamp())
+            .execute(connection)
+            .await {
+                Ok(row) if row.rows_affected() > 0 => {
+                    result.result = true;
+                    result.message = "API key revoked".to_string();
+                }
+                Ok(_) => {
+                    result.message = "API key not found or already revoked".to_string();
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to update api_keys: {}", e));
+                }
+            }
+
+        result
+    }
+
+    /// Cek key + scope, sekaligus catat `last_used_at`.
+    /// Scope juga dicek ulang terhadap akses pemilik key saat ini: pemilik yang kehilangan role kehilangan scope-nya,
+    /// pemilik yang dinonaktifkan / dihapus membuat key tidak berlaku.
+    pub async fn verify(api_key: &str, required_scope: &str) -> Result<ApiKeyCheck, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let now = GenericService::get_timestamp();
+
+        let row = sqlx::query(r#"UPDATE api_keys SET last_used_at = $2
+            WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > $2)
+            RETURNING api_key_id, created_by, name, key_prefix, scopes,
+                to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at,
+                to_char(expires_at, 'YYYY-MM-DD HH24:MI:SS') AS expires_at,
+                to_char(last_used_at, 'YYYY-MM-DD HH24:MI:SS') AS last_used_at,
+                to_char(revoked_at, 'YYYY-MM-DD HH24:MI:SS') AS revoked_at"#)
+            .bind(hash_token(api_key.trim()))
+            .bind(now)
+            .fetch_optional(connection)
+            .await
+            .map_err(|e| format!("Failed to update api_keys: {}", e))?;
+
+        let Some(row) = row else { return Ok(ApiKeyCheck::Invalid) };
+        let info = Self::info_from_row(&row);
+
+        if !info.scopes.iter().any(|granted| Self::scope_allows(granted, required_scope)) {
+            return Ok(ApiKeyCheck::MissingScope);
+        }
+
+        // Key lama tanpa pemilik (dibuat sebelum pemilik dicatat) hanya dibatasi scope-nya sendiri
+        let Some(owner) = info.created_by.filter(|owner| *owner > 0) else { return Ok(ApiKeyCheck::Valid(info)) };
+
+        let owner_row = sqlx::query(r#"SELECT COALESCE(U.disable_login, false) OR U.deleted_at IS NOT NULL AS inactive,
+                ARRAY(SELECT DISTINCT P.name FROM user_roles UR
+                    JOIN role_permissions RP ON RP.role_id = UR.role_id
+                    JOIN permissions P ON P.permission_id = RP.permission_id
+                    WHERE UR.user_nid = U.web_cif_id) AS permissions
+            FROM users U
+            WHERE U.web_cif_id = $1"#)
+            .bind(owner)
+            .fetch_optional(connection)
+            .await
+            .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+        let Some(owner_row) = owner_row else { return Ok(ApiKeyCheck::Invalid) };
+
+        if owner_row.get::<bool, _>("inactive") {
+            return Ok(ApiKeyCheck::Invalid);
+        }
+
+        let owner_scopes = Self::user_scopes(&owner_row.get::<Vec<String>, _>("permissions"));
+
+        if owner_scopes.iter().any(|granted| Self::scope_allows(granted, required_scope)) {
+            Ok(ApiKeyCheck::Valid(info))
+        } else {
+            Ok(ApiKeyCheck::MissingScope)
+        }
+    }
+
+    /// Cabut semua key milik user (dipanggil saat akun dianonimkan)
+    pub async fn revoke_owned_by(trans: &mut sqlx::PgConnection, usernid: i32) -> Result<u64, String> {
+        sqlx::query(r#"UPDATE api_keys SET revoked_at = $2 WHERE created_by = $1 AND revoked_at IS NULL"#)
+            .bind(usernid)
+            .bind(GenericService::get_timestamp())
+            .execute(trans)
+            .await
+            .map(|done| done.rows_affected())
+            .map_err(|e| format!("Failed to update api_keys: {}", e))
+    }
+}
@@ -0,0 +1,198 @@
+use actix_web::HttpRequest;
+use sqlx::{Postgres, QueryBuilder, Row};
+
+use crate::{middleware::{jwt_session::Claims, model::{ActionResult, SecurityEventInfo, SecurityEventQuery}}, services::generic_service::GenericService, CONNECTION};
+
+/// Jenis kejadian yang dicatat di `security_events`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecurityEventType {
+    LoginSuccess,
+    LoginFailed,
+    Logout,
+    Activation,
+    PasswordResetRequested,
+    PasswordChanged,
+    OAuthLinked,
+    PasskeyRegistered,
+    ImpersonationStarted,
+    ImpersonationStopped,
+    ImpersonationWriteBlocked,
+}
+
+impl SecurityEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEventType::LoginSuccess => "login_success",
+            SecurityEventType::LoginFailed => "login_failed",
+            SecurityEventType::Logout => "logout",
+            SecurityEventType::Activation => "activation",
+            SecurityEventType::PasswordResetRequested => "password_reset_requested",
+            SecurityEventType::PasswordChanged => "password_changed",
+            SecurityEventType::OAuthLinked => "oauth_linked",
+            SecurityEventType::PasskeyRegistered => "passkey_registered",
+            SecurityEventType::ImpersonationStarted => "impersonation_started",
+            SecurityEventType::ImpersonationStopped => "impersonation_stopped",
+            SecurityEventType::ImpersonationWriteBlocked => "impersonation_write_blocked",
+        }
+    }
+}
+
+/// Satu kejadian yang akan ditulis. Biasanya dibuat dari request / claims lalu diisi sisanya:
+/// `SecurityEvent { success: false, ..SecurityEvent::from_request(SecurityEventType::LoginFailed, req) }`
+#[derive(Debug, Clone)]
+pub struct SecurityEvent {
+    pub event_type: SecurityEventType,
+    pub user_nid: Option<i32>,
+    pub email: Option<String>,
+    pub success: bool,
+    pub ip_address: Option<String>,
+    pub device_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    /// Admin yang sebenarnya melakukan aksi saat impersonation
+    pub actor_nid: Option<i32>,
+}
+
+impl SecurityEvent {
+    pub fn from_request(event_type: SecurityEventType, req: &HttpRequest) -> Self {
+        Self {
+            event_type,
+            user_nid: None,
+            email: None,
+            success: true,
+            ip_address: Some(GenericService::get_ip_address(req)),
+            device_name: Some(GenericService::get_device_name(req)),
+            user_agent: Some(GenericService::get_user_agent(req)),
+            detail: None,
+            actor_nid: None,
+        }
+    }
+
+    /// IP / device diambil dari claims, yang diisi dari request saat login.
+    /// Token impersonation otomatis menandai event dengan ID admin-nya.
+    pub fn from_claims(event_type: SecurityEventType, claims: &Claims) -> Self {
+        Self {
+            event_type,
+            user_nid: Some(claims.usernid),
+            email: Some(claims.email.clone()),
+            success: true,
+            ip_address: claims.ip_address.clone(),
+            device_name: claims.comp_name.clone(),
+            user_agent: claims.user_agent.clone(),
+            detail: None,
+            actor_nid: claims.impersonator,
+        }
+    }
+}
+
+/// 📝 Audit log keamanan akun
+pub struct AuditService;
+
+impl AuditService {
+
+    /// Tulis event. Gagal menulis audit tidak boleh menggagalkan proses utamanya, cukup di-log.
+    pub async fn record(event: SecurityEvent) {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        if let Err(e) = sqlx::query(r#"INSERT INTO security_events
+            (user_nid, email, event_type, success, ip_address, device_name, user_agent, detail, actor_nid, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#)
+            .bind(event.user_nid)
+            .bind(event.email.map(|email| email.trim().to_lowercase()))
+            .bind(event.event_type.as_str())
+            .bind(event.success)
+            .bind(event.ip_address)
+            .bind(event.device_name)
+            .bind(event.user_agent)
+            .bind(event.detail)
+            .bind(event.actor_nid)
+            .bind(GenericService::get_timestamp())
+            .execute(connection)
+            .await {
+                println!("❌ Security Event Error: {}", e);
+            }
+    }
+
+    /// Cari event dengan filter opsional, terbaru di atas. `limit` default 50, maksimal 200.
+    pub async fn search(filter: SecurityEventQuery) -> ActionResult<Vec<SecurityEventInfo>, String> {
+        let mut result: ActionResult<Vec<SecurityEventInfo>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let parse_date = |value: &Option<String>| -> Result<Option<chrono::NaiveDate>, String> {
+            match value.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+                Some(value) => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map(Some)
+                    .map_err(|_| format!("Invalid date: {}, expected YYYY-MM-DD", value)),
+                None => Ok(None),
+            }
+        };
+
+        let (from, to) = match (parse_date(&filter.from), parse_date(&filter.to)) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(r#"SELECT event_id, user_nid, email, event_type, success,
+                ip_address, device_name, user_agent, detail, actor_nid,
+                to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at
+            FROM security_events WHERE 1 = 1"#);
+
+        if let Some(user_nid) = filter.user_nid {
+            query.push(" AND user_nid = ").push_bind(user_nid);
+        }
+        if let Some(actor_nid) = filter.actor_nid {
+            query.push(" AND actor_nid = ").push_bind(actor_nid);
+        }
+        if let Some(email) = filter.email.filter(|value| !value.trim().is_empty()) {
+            query.push(" AND email = ").push_bind(email.trim().to_lowercase());
+        }
+        if let Some(event_type) = filter.event_type.filter(|value| !value.trim().is_empty()) {
+            query.push(" AND event_type = ").push_bind(event_type.trim().to_string());
+        }
+        if let Some(ip_address) = filter.ip_address.filter(|value| !value.trim().is_empty()) {
+            query.push(" AND ip_address = ").push_bind(ip_address.trim().to_string());
+        }
+        if let Some(success) = filter.success {
+            query.push(" AND success = ").push_bind(success);
+        }
+        if let Some(from) = from {
+            query.push(" AND created_at >= ").push_bind(from.and_hms_opt(0, 0, 0));
+        }
+        if let Some(to) = to {
+            // `to` inklusif sampai akhir hari
+            query.push(" AND created_at < ").push_bind(to.succ_opt().and_then(|date| date.and_hms_opt(0, 0, 0)));
+        }
+
+        query.push(" ORDER BY created_at DESC, event_id DESC LIMIT ")
+            .push_bind(filter.limit.unwrap_or(50).clamp(1, 200))
+            .push(" OFFSET ")
+            .push_bind(filter.offset.unwrap_or(0).max(0));
+
+        match query.build().fetch_all(connection).await {
+            Ok(rows) => {
+                result.result = true;
+                result.data = Some(rows.iter().map(|row| SecurityEventInfo {
+                    event_id: row.get("event_id"),
+                    user_nid: row.get("user_nid"),
+                    email: row.get("email"),
+                    event_type: row.get("event_type"),
+                    success: row.get("success"),
+                    ip_address: row.get("ip_address"),
+                    device_name: row.get("device_name"),
+                    user_agent: row.get("user_agent"),
+                    detail: row.get("detail"),
+                    actor_nid: row.get("actor_nid"),
+                    created_at: row.get("created_at"),
+                }).collect());
+            }
+            Err(e) => {
+                result.error = Some(format!("Failed to fetch security_events: {}", e));
+            }
+        }
+
+        result
+    }
+}
@@ -1,26 +1,56 @@
 use std::collections::HashMap;
+use chrono::TimeZone;
 use actix_web::HttpRequest;
+use sqlx::postgres::PgRow;
 use sqlx::PgPool;
 use sqlx::Row;
 
-use crate::middleware::jwt_session::validate_jwt;
+use crate::middleware::crypto::hash_token;
+use crate::middleware::jwt_session::create_jwt;
+use crate::middleware::jwt_session::refresh_token_ttl;
+use crate::middleware::jwt_session::session_idle_timeout;
+use crate::middleware::jwt_session::session_max_age;
 use crate::middleware::model::ChangePasswordRequest;
+use crate::middleware::model::ExternalIdentity;
 use crate::middleware::model::RegisterRequest;
+use crate::middleware::model::ResendActivationRequest;
 use crate::middleware::model::ResetPasswordRequest;
+use crate::middleware::model::SessionTokens;
+use crate::middleware::model::UserSession;
 use crate::CONNECTION;
 use crate::SECRETS;
-use crate::{middleware::{crypto::encrypt_text, jwt_session::Claims, model::{ActionResult, LoginRequest}}, services::generic_service::GenericService};
+use crate::{middleware::{jwt_session::Claims, model::{ActionResult, LoginRequest}, password::{hash_password, verify_password, PasswordCheck}}, services::generic_service::GenericService};
 
+use super::audit_service::{AuditService, SecurityEvent, SecurityEventType};
 use super::mail_service::MailService;
+use super::role_service::RoleService;
+use super::token_revocation_service::TokenRevocationService;
 
 pub struct AuthService;
 
 impl AuthService {
+    /// Login email + password, hasil gagal dicatat di `security_events` (login sukses dicatat saat session dibuat)
     pub async fn login(request: LoginRequest, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let email = request.email.clone();
+        let result = Self::password_login(request, req, app_name).await;
+
+        if result.data.is_none() {
+            AuditService::record(SecurityEvent {
+                email,
+                success: false,
+                detail: Some(result.error.clone().unwrap_or_else(|| result.message.clone())),
+                ..SecurityEvent::from_request(SecurityEventType::LoginFailed, req)
+            }).await;
+        }
+
+        result
+    }
+
+    async fn password_login(request: LoginRequest, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
 
         let connection: &PgPool = CONNECTION.get().unwrap();
         let mut result = ActionResult::default();
-        let enc_password = encrypt_text(request.password.unwrap_or_default());
+        let password = request.password.unwrap_or_default();
 
         let query_result = sqlx::query(
             r#"
@@ -28,44 +58,58 @@ impl AuthService {
                 B.autonid AS user_id, 
                 B.fullname,
                 A.email, 
+                A.password,
                 A.disable_login, 
                 A.last_login, 
                 A.picture, 
                 A.register_date
             FROM users A
             LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
-            WHERE A.email = $1 AND A.password = $2
+            WHERE A.email = $1
             "#
         ).bind(request.email.clone().unwrap_or_default())
-        .bind(enc_password)
         .fetch_one(connection)
         .await;
 
         match query_result {
             Ok(row) => {
+                let stored_password = row.try_get::<String, _>("password").unwrap_or_default();
+
+                match verify_password(&password, &stored_password).await {
+                    PasswordCheck::Invalid => {
+                        result.message = format!("Incorrect email or password");
+                        return result;
+                    }
+                    PasswordCheck::NeedsRehash => {
+                        // Migrasi transparan dari AES-CTR / parameter lama ke Argon2id
+                        match hash_password(&password).await {
+                            Ok(new_hash) => {
+                                if let Err(e) = sqlx::query(r#"UPDATE users SET password = $1 WHERE email = $2 AND password = $3"#)
+                                    .bind(&new_hash)
+                                    .bind(request.email.clone().unwrap_or_default())
+                                    .bind(&stored_password)
+                                    .execute(connection)
+                                    .await {
+                                        println!("❌ Rehash Password Error: {}", e);
+                                    }
+                            }
+                            Err(e) => println!("❌ Rehash Password Error: {}", e),
+                        }
+                    }
+                    PasswordCheck::Valid => {}
+                }
+
                 if row.get("disable_login") {
                     result.error = Some("Login disabled, please check email to activation".to_string());
                     return result;
                 }
 
                 result.result = true;
-                result.data = Some(Claims {
-                    usernid: row.try_get::<i32, _>("user_id").unwrap_or(0),
-                    fullname: row.try_get::<String, _>("fullname").unwrap_or_default(),
-                    email: row.try_get::<String, _>("email").unwrap_or_default(),
-                    disabled_login: row.try_get::<bool, _>("disable_login").unwrap_or(false),
-                    picture: row.try_get::<Option<String>, _>("picture").unwrap_or_default(),
-                    register_date: row.try_get::<chrono::DateTime<chrono::Utc>, _>("register_date").unwrap_or_else(|_| chrono::Utc::now()),
-                    result: true,
-                    expired_token: 0,
-                    expired_date: "".to_string(),
-                    exp: 0,
-                    comp_name: Some(GenericService::get_device_name(req)),
-                    ip_address: Some(GenericService::get_ip_address(req)),
-                    app_name: Some(app_name.to_string()),
-                })
+                result.data = Some(Self::claims_from_row(&row, req, app_name))
             }
             Err(e) => {
+                // Email tidak terdaftar: tetap verifikasi ke hash dummy supaya waktu respons tidak membocorkan akun
+                let _ = verify_password(&password, "").await;
                 result.message = format!("Incorrect email or password");
                 println!("❌ Login Error: {}", e);
             }
@@ -74,15 +118,372 @@ impl AuthService {
         result
     }
 
+    /// Login tanpa password (OTP email, dll): cari user aktif berdasarkan email
+    pub async fn login_by_email(email: &str, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let connection: &PgPool = CONNECTION.get().unwrap();
+        let mut result = ActionResult::default();
+
+        let query_result = sqlx::query(
+            r#"
+            SELECT 
+                B.autonid AS user_id, 
+                B.fullname,
+                A.email, 
+                A.disable_login, 
+                A.picture, 
+                A.register_date
+            FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE lower(A.email) = lower($1)
+            "#
+        ).bind(email)
+        .fetch_optional(connection)
+        .await;
+
+        match query_result {
+            Ok(Some(row)) => {
+                if row.get("disable_login") {
+                    result.message = "Login disabled, please check email to activation".to_string();
+                    return result;
+                }
+
+                result.result = true;
+                result.data = Some(Self::claims_from_row(&row, req, app_name));
+            }
+            Ok(None) => {
+                result.message = "User not found".to_string();
+            }
+            Err(e) => {
+                result.error = Some(format!("Failed to fetch users: {}", e));
+            }
+        }
+
+        result
+    }
+
+    /// Claims user berdasarkan ID tanpa cek `disable_login` (dipakai impersonation admin)
+    pub async fn claims_for_user(usernid: i32, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let connection: &PgPool = CONNECTION.get().unwrap();
+        let mut result = ActionResult::default();
+
+        match sqlx::query(
+            r#"
+            SELECT 
+                B.autonid AS user_id, 
+                B.fullname,
+                A.email, 
+                A.disable_login, 
+                A.picture, 
+                A.register_date
+            FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE A.web_cif_id = $1 AND A.deleted_at IS NULL
+            "#
+        ).bind(usernid)
+        .fetch_optional(connection)
+        .await {
+            Ok(Some(row)) => {
+                result.result = true;
+                result.data = Some(Self::claims_from_row(&row, req, app_name));
+            }
+            Ok(None) => {
+                result.message = "User not found".to_string();
+            }
+            Err(e) => {
+                result.error = Some(format!("Failed to fetch users: {}", e));
+            }
+        }
+
+        result
+    }
+
+    fn claims_from_row(row: &PgRow, req: &HttpRequest, app_name: &str) -> Claims {
+        Claims {
+            usernid: row.try_get::<i32, _>("user_id").unwrap_or(0),
+            fullname: row.try_get::<String, _>("fullname").unwrap_or_default(),
+            email: row.try_get::<String, _>("email").unwrap_or_default(),
+            disabled_login: row.try_get::<bool, _>("disable_login").unwrap_or(false),
+            picture: row.try_get::<Option<String>, _>("picture").unwrap_or_default(),
+            register_date: row.try_get::<chrono::DateTime<chrono::Utc>, _>("register_date").unwrap_or_else(|_| chrono::Utc::now()),
+            result: true,
+            expired_token: 0,
+            expired_date: "".to_string(),
+            exp: 0,
+            comp_name: Some(GenericService::get_device_name(req)),
+            ip_address: Some(GenericService::get_ip_address(req)),
+            app_name: Some(app_name.to_string()),
+            user_agent: Some(GenericService::get_user_agent(req)),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            jti: String::new(),
+            iat: 0,
+            impersonator: None,
+        }
+    }
+
+    /// Login via provider eksternal (OIDC / OAuth2): cari user lewat `user_identities`, kalau belum ada tautkan ke email
+    /// yang sama (wajib terverifikasi di provider), kalau tetap tidak ada buat `users` + `user_kyc` baru.
+    pub async fn external_login(identity: ExternalIdentity, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let mut result: ActionResult<Claims, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        if identity.email.is_empty() {
+            result.message = format!("Your {} account has no email address", identity.provider);
+            return result;
+        }
+
+        let mut trans = match connection.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                result.error = Some(format!("Database error: {}", e));
+                return result;
+            }            
+        };
+
+        let linked = match sqlx::query(r#"SELECT I.user_nid, A.activate_time IS NOT NULL AS activated, A.disable_login
+                FROM user_identities I
+                JOIN users A ON A.web_cif_id = I.user_nid
+                WHERE I.provider = $1 AND I.subject = $2"#)
+            .bind(&identity.provider)
+            .bind(&identity.subject)
+            .fetch_optional(&mut *trans)
+            .await {
+                Ok(row) => row,
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch user_identities: {}", e));
+                    return result;
+                }
+            };
+
+        let (web_cif_id, newly_linked): (i32, bool) = match linked {
+            Some(row) => {
+                let web_cif_id: i32 = row.get("user_nid");
+
+                if row.try_get("activated").unwrap_or(false) && row.try_get("disable_login").unwrap_or(false) {
+                    result.message = "Login disabled, please contact support".to_string();
+                    return result;
+                }
+
+                if let Err(e) = sqlx::query(r#"UPDATE user_identities SET email = $1, last_login_at = $2 WHERE provider = $3 AND subject = $4"#)
+                    .bind(&identity.email)
+                    .bind(GenericService::get_timestamp())
+                    .bind(&identity.provider)
+                    .bind(&identity.subject)
+                    .execute(&mut *trans)
+                    .await {
+                        result.error = Some(format!("Failed to update user_identities: {}", e));
+                        return result;
+                    };
+
+                (web_cif_id, false)
+            }
+            None => {
+                // Tanpa email terverifikasi akun orang lain bisa diambil alih lewat email yang sama
+                if !identity.email_verified {
+                    result.message = format!("Your {} email is not verified", identity.provider);
+                    return result;
+                }
+
+                let web_cif_id = match Self::link_or_create_user(&mut trans, &identity, req).await {
+                    Ok(Ok(web_cif_id)) => web_cif_id,
+                    Ok(Err(message)) => {
+                        result.message = message;
+                        return result;
+                    }
+                    Err(e) => {
+                        result.error = Some(e);
+                        return result;
+                    }
+                };
+
+                if let Err(e) = sqlx::query(r#"INSERT INTO user_identities (user_nid, provider, subject, email, created_at, last_login_at)
+                    VALUES ($1, $2, $3, $4, $5, $5)"#)
+                    .bind(web_cif_id)
+                    .bind(&identity.provider)
+                    .bind(&identity.subject)
+                    .bind(&identity.email)
+                    .bind(GenericService::get_timestamp())
+                    .execute(&mut *trans)
+                    .await {
+                        result.error = Some(format!("Failed to insert user_identities: {}", e));
+                        return result;
+                    };
+
+                (web_cif_id, true)
+            }
+        };
+
+        let user_row = match sqlx::query(
+            r#"
+            SELECT 
+                B.autonid AS user_id, 
+                B.fullname,
+                A.email, 
+                A.disable_login, 
+                A.picture, 
+                A.register_date
+            FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE A.web_cif_id = $1
+            "#
+        ).bind(web_cif_id)
+        .fetch_one(&mut *trans)
+        .await {
+            Ok(row) => row,
+            Err(e) => {
+                result.error = Some(format!("Failed to fetch users: {}", e));
+                return result;
+            }
+        };
+
+        if let Err(e) = trans.commit().await {
+            result.error = Some(format!("Failed to commit transaction: {}", e));
+            return result;
+        }
+
+        let claims = Self::claims_from_row(&user_row, req, app_name);
+
+        if newly_linked {
+            AuditService::record(SecurityEvent {
+                detail: Some(identity.provider.clone()),
+                ..SecurityEvent::from_claims(SecurityEventType::OAuthLinked, &claims)
+            }).await;
+        }
+
+        result.result = true;
+        result.data = Some(claims);
+        result
+    }
+
+    /// Tautkan ke user dengan email yang sama, atau buat `users` + `user_kyc` + `user_request` baru.
+    /// `Ok(Err(..))` untuk penolakan yang ditampilkan ke user.
+    async fn link_or_create_user(trans: &mut sqlx::Transaction<'_, sqlx::Postgres>, identity: &ExternalIdentity, req: &HttpRequest) -> Result<Result<i32, String>, String> {
+        let existing = sqlx::query(r#"SELECT web_cif_id, activate_time IS NOT NULL AS activated, disable_login,
+                EXISTS (SELECT 1 FROM user_identities I WHERE I.user_nid = users.web_cif_id AND I.provider = $2) AS has_identity
+                FROM users WHERE lower(email) = lower($1)"#)
+            .bind(&identity.email)
+            .bind(&identity.provider)
+            .fetch_optional(&mut **trans)
+            .await
+            .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+        if let Some(row) = existing {
+            let web_cif_id: i32 = row.get("web_cif_id");
+            let activated: bool = row.try_get("activated").unwrap_or(false);
+            let disable_login: bool = row.try_get("disable_login").unwrap_or(false);
+
+            // Akun sudah aktif tapi di-disable -> jangan dibuka lewat provider eksternal
+            if activated && disable_login {
+                return Ok(Err("Login disabled, please contact support".to_string()));
+            }
+
+            if row.try_get("has_identity").unwrap_or(false) {
+                return Ok(Err(format!("Email is already linked to another {} account", identity.provider)));
+            }
+
+            // Akun belum aktif = pemilik email belum terbukti, password bisa saja dipasang orang lain (pre-account takeover).
+            // Password dan session yang ada dibuang, pemilik email yang login lewat provider jadi satu-satunya akses.
+            if !activated {
+                sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1"#)
+                    .bind(web_cif_id)
+                    .execute(&mut **trans)
+                    .await
+                    .map_err(|e| format!("Failed to delete cookies: {}", e))?;
+            }
+
+            // Email sudah diverifikasi provider, jadi sekalian dianggap aktivasi
+            sqlx::query(r#"UPDATE users 
+                SET picture = COALESCE(NULLIF(picture, ''), $1),
+                password = CASE WHEN activate_time IS NULL THEN '' ELSE password END,
+                activate_time = COALESCE(activate_time, $2),
+                disable_login = false
+                WHERE web_cif_id = $3"#)
+                .bind(&identity.picture)
+                .bind(GenericService::get_timestamp())
+                .bind(web_cif_id)
+                .execute(&mut **trans)
+                .await
+                .map_err(|e| format!("Failed to update users: {}", e))?;
+
+            return Ok(Ok(web_cif_id));
+        }
+
+        let fullname = identity.name.clone().unwrap_or_else(|| identity.email.clone());
+
+        let auto_nid: i32 = sqlx::query(r#"
+            INSERT INTO user_kyc 
+            (email, mobile_phone, fullname, sales, stage, cif_nid, change_nid, pending_cif_nid,
+            is_rejected, is_finished, is_revised, is_imported, save_time, last_update, save_ip_address)
+            VALUES
+            ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15)
+            RETURNING autonid;"#)
+            .bind(&identity.email)
+            .bind("") // mobile_phone diisi saat onboarding
+            .bind(&fullname)
+            .bind(0i32) // sales
+            .bind(1i32) // stage
+            .bind(0i32) // cifnid
+            .bind(0i32) // changenid
+            .bind(0i32) // pendingcifnid
+            .bind(false) // isrejected
+            .bind(false) // isfinished
+            .bind(false) // isrevised
+            .bind(false) // isimported
+            .bind(GenericService::get_timestamp())
+            .bind(GenericService::get_timestamp())
+            .bind(GenericService::get_ip_address(req))
+            .fetch_one(&mut **trans)
+            .await
+            .map(|row| row.get("autonid"))
+            .map_err(|e| format!("Failed to insert user_kyc: {}", e))?;
+
+        sqlx::query(r#"
+            INSERT INTO users 
+            (web_cif_id, email, handphone, activate_code, password, register_date,
+            disable_login, otp_generated_link, otp_generated_link_date, picture, google_id, client_category, activate_time)
+            VALUES
+            ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13);"#)
+            .bind(auto_nid)
+            .bind(&identity.email)
+            .bind("") // handphone
+            .bind(GenericService::random_string(20))
+            .bind("") // password kosong, login hanya via provider sampai user reset password
+            .bind(GenericService::get_timestamp())
+            .bind(false)
+            .bind(GenericService::random_string(70))
+            .bind(GenericService::get_timestamp())
+            .bind(identity.picture.clone().unwrap_or_default())
+            .bind("") // google_id lama, tautan sekarang di user_identities
+            .bind(None::<i32>) // client_category dipilih saat onboarding
+            .bind(GenericService::get_timestamp())
+            .execute(&mut **trans)
+            .await
+            .map_err(|e| format!("Failed to insert users: {}", e))?;
+
+        sqlx::query(r#"INSERT INTO user_request (web_cif_nid, referal) VALUES ($1, $2);"#)
+            .bind(auto_nid)
+            .bind("")
+            .execute(&mut **trans)
+            .await
+            .map_err(|e| format!("Failed to insert user_request: {}", e))?;
+
+        Ok(Ok(auto_nid))
+    }
+
     pub async fn register(request: RegisterRequest) -> ActionResult<String, String> {
 
         let connection = CONNECTION.get().expect("DB_POOL not initialized");
-        let secrets = SECRETS.get().expect("SECRETS not initialized");
-        let front_url = secrets.get("FRONT_URL").expect("secret was not found");
-        let enc_password = encrypt_text(request.password.unwrap_or_default());
 
         let mut result = ActionResult::default();
 
+        let enc_password = match hash_password(&request.password.clone().unwrap_or_default()).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                result.error = Some(format!("Failed to hash password: {}", e));
+                return result;
+            }
+        };
+
         let mut trans = match connection.begin().await {
             Ok(t) => t,
             Err(e) => {
@@ -178,12 +579,29 @@ impl AuthService {
             return result;
         }
 
+        Self::send_activation_mail(request.full_name, request.email.unwrap_or_default(), &otp_generated_link).await;
+
+        result.result = true;
+        result.message = "User registered successfully".into();
+
+        return result;
+    }
+
+    fn activation_setting(key: &str, default: i64) -> i64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    async fn send_activation_mail(full_name: Option<String>, email: String, otp_generated_link: &str) {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let front_url = secrets.get("FRONT_URL").expect("secret was not found");
+
         let mut mail_data = HashMap::new();
-        mail_data.insert("username".to_string(), request.full_name);
+        mail_data.insert("username".to_string(), full_name);
         mail_data.insert("front_url".to_string(), Some(format!("{}/activation/{}", front_url, otp_generated_link)));
         mail_data.insert("company_name".to_string(), Some("PT. TECH SNAKE SYSTEM".to_string()));
         mail_data.insert("subject".to_string(), Some("Verifikasi Akun Anda".to_string()));
-        mail_data.insert("email".to_string(), request.email);
+        mail_data.insert("email".to_string(), Some(email));
         mail_data.insert("title".to_string(), Some("CUSTOMER ONBOARDING ACTIVATION".to_string()));
 
         let mail_result : ActionResult<String, String> = MailService::send(mail_data, "activation").await;
@@ -191,17 +609,14 @@ impl AuthService {
         if let Some(e) = mail_result.error {
             println!("❌ Mail Error: {}", e);
         }
-
-        result.result = true;
-        result.message = "User registered successfully".into();
-
-        return result;
     }
 
-    pub async fn activation(activation_url: String) -> ActionResult<String, String> {
+    /// Aktivasi akun. Link berlaku `ACTIVATION_LINK_HOURS` (default 24 jam) sejak `otp_generated_link_date`
+    pub async fn activation(activation_url: String, req: &HttpRequest) -> ActionResult<String, String> {
         let mut result = ActionResult::default();
 
         let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let link_cutoff = GenericService::get_timestamp() - chrono::Duration::hours(Self::activation_setting("ACTIVATION_LINK_HOURS", 24));
 
         let mut trans = match connection.begin().await {
             Ok(trans) => trans,
@@ -211,43 +626,48 @@ impl AuthService {
             }
         };
 
-        let query_result = match sqlx::query(r#"SELECT web_cif_id, activate_time, count_resend_activation 
+        let query_result = match sqlx::query(r#"SELECT web_cif_id, activate_time::TEXT AS activated_at, otp_generated_link_date > $2 AS link_active
                     FROM users 
-                    WHERE otp_generated_link = $1;"#)
+                    WHERE otp_generated_link = $1
+                    FOR UPDATE;"#)
             .bind(&activation_url)
+            .bind(link_cutoff)
             .fetch_optional(&mut *trans).await {
                 Ok(row) => row,
                 Err(e) => {
-                    result.error = Some(format!("Failed to insert users: {}", e));
+                    result.error = Some(format!("Failed to fetch users: {}", e));
                     return result;
                 }
             
         };
 
-        let (web_cif_id, activate_time, count_resend_activation): (Option<i32>, Option<chrono::DateTime<chrono::Utc>>, Option<i32>) = match query_result {
-            Some(row) => (row.get("web_cif_id"), row.get("activate_time"), row.get("count_resend_activation")),
+        let (web_cif_id, activated_at, link_active): (i32, Option<String>, Option<bool>) = match query_result {
+            Some(row) => (row.get("web_cif_id"), row.get("activated_at"), row.get("link_active")),
             None => {
                 result.message = "Invalid activation link".into();
                 return result;
             }
         };
 
-        if let Some(count_resend_activation) = count_resend_activation {
-            if count_resend_activation > 0 {
-                result.message = format!("You have already activated your account at {}", activate_time.unwrap_or_default().to_string());
-                return result;
-            }
+        if let Some(activated_at) = activated_at {
+            result.message = format!("You have already activated your account at {}", activated_at);
+            return result;
+        }
+
+        if !link_active.unwrap_or(false) {
+            result.message = "Activation link has expired, please request a new one".into();
+            return result;
         }
 
         if let Err(e) = sqlx::query(r#"UPDATE users
-            SET count_resend_activation = count_resend_activation + 1, activate_time = $2, disable_login = $3
+            SET activate_time = $2, disable_login = $3
             WHERE web_cif_id = $1"#)
             .bind(web_cif_id)
             .bind(GenericService::get_timestamp())
             .bind(false)
             .execute(&mut *trans).await {
                 let _ = trans.rollback().await;
-                result.error = Some(format!("Failed to insert users: {}", e));
+                result.error = Some(format!("Failed to update users: {}", e));
                 return result;
             }
 
@@ -256,42 +676,147 @@ impl AuthService {
             return result;
         }
 
+        AuditService::record(SecurityEvent {
+            user_nid: Some(web_cif_id),
+            ..SecurityEvent::from_request(SecurityEventType::Activation, req)
+        }).await;
+
         result.result = true;
         result.message = "Account activated successfully".into();
         return result;
     }
 
-    pub async fn check_session(session: Claims, token: String, cookies: String, delete: bool, update: bool, exist: bool, app_name: &str) -> ActionResult<Claims, String> {
-        let mut result: ActionResult<Claims, String> = ActionResult::default();
+    /// Kirim ulang link aktivasi: link lama langsung tidak berlaku, dibatasi cooldown `ACTIVATION_RESEND_SECONDS` (default 120)
+    pub async fn resend_activation(request: ResendActivationRequest) -> ActionResult<i64, String> {
+        let mut result = ActionResult::default();
 
         let connection = CONNECTION.get().expect("DB_POOL not initialized");
-        let active_token = if cookies.is_empty() { token.clone() } else { cookies.clone() };
+        let email = request.email.unwrap_or_default();
+        let cooldown = Self::activation_setting("ACTIVATION_RESEND_SECONDS", 120);
+        let now = GenericService::get_timestamp();
 
-        let mut trans = match connection.begin().await {
-            Ok(t) => t,
-            Err(e) => {
-                result.error = Some(format!("Database error: {}", e));
-                return result;
-            }            
+        // Jawaban sama untuk email tidak terdaftar / sudah aktif supaya tidak bisa dipakai cek akun
+        let generic_message = "If the account is waiting for activation, a new link has been sent";
+
+        let row = match sqlx::query(r#"
+            SELECT B.fullname, A.email, A.activate_time IS NOT NULL AS activated,
+                EXTRACT(EPOCH FROM (A.otp_generated_link_date + make_interval(secs => $2) - $3))::BIGINT AS wait_seconds
+            FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE A.email = $1"#)
+            .bind(&email)
+            .bind(cooldown as f64)
+            .bind(now)
+            .fetch_optional(connection)
+            .await {
+                Ok(row) => row,
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            };
+
+        let Some(row) = row else {
+            result.result = true;
+            result.message = generic_message.into();
+            return result;
         };
 
-        if exist {
-            // println!("Check Session");
-            let row_count: i64 = match sqlx::query(r#"SELECT COUNT(*) as count FROM cookies WHERE user_nid = $1 AND token_cookie = $2"#)
+        if row.get::<bool, _>("activated") {
+            result.result = true;
+            result.message = generic_message.into();
+            return result;
+        }
+
+        let wait_seconds: i64 = row.try_get::<Option<i64>, _>("wait_seconds").unwrap_or_default().unwrap_or(0);
+        if wait_seconds > 0 {
+            result.message = "Please wait before requesting another activation link".into();
+            result.data = Some(wait_seconds);
+            return result;
+        }
+
+        // Rotasi link, cooldown dicek ulang di WHERE supaya request paralel tidak kirim dua email
+        let otp_generated_link: Option<String> = match sqlx::query(r#"UPDATE users
+            SET otp_generated_link = $1, otp_generated_link_date = $2, count_resend_activation = count_resend_activation + 1
+            WHERE email = $3 AND activate_time IS NULL
+                AND (otp_generated_link_date IS NULL OR otp_generated_link_date <= $4)
+            RETURNING otp_generated_link"#)
+            .bind(GenericService::random_string(70))
+            .bind(now)
+            .bind(&email)
+            .bind(now - chrono::Duration::seconds(cooldown))
+            .fetch_optional(connection)
+            .await {
+                Ok(row) => row.map(|r| r.get("otp_generated_link")),
+                Err(e) => {
+                    result.error = Some(format!("Failed to update users: {}", e));
+                    return result;
+                }
+            };
+
+        let Some(otp_generated_link) = otp_generated_link else {
+            result.message = "Please wait before requesting another activation link".into();
+            result.data = Some(cooldown);
+            return result;
+        };
+
+        Self::send_activation_mail(row.get("fullname"), row.get("email"), &otp_generated_link).await;
+
+        result.result = true;
+        result.message = generic_message.into();
+        result
+    }
+
+    pub async fn check_session(session: Claims, token: String, cookies: String, delete: bool, update: bool, exist: bool, app_name: &str) -> ActionResult<Claims, String> {
+        let mut result: ActionResult<Claims, String> = ActionResult::default();
+
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let active_token = if cookies.is_empty() { token.clone() } else { cookies.clone() };
+        let mut new_session = false;
+
+        let mut trans = match connection.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                result.error = Some(format!("Database error: {}", e));
+                return result;
+            }            
+        };
+
+        if exist {
+            // println!("Check Session");
+            let session_row = match sqlx::query(r#"SELECT last_update, COALESCE(created_at, last_update) AS created_at
+                FROM cookies WHERE user_nid = $1 AND token_cookie = $2"#)
                 .bind(session.usernid)
                 .bind(&active_token)
-                .fetch_one(&mut *trans)
+                .fetch_optional(&mut *trans)
                 .await {
-                    Ok(row) => row.get("count"),
+                    Ok(row) => row,
                     Err(e) => {
                         println!("❌ Check Session Error: {}", e);
-                        0i64
+                        None
                     }
                 };
 
-            if row_count == 0 {
+            let Some(session_row) = session_row else {
                 result.error = Some("Session has expired".to_string());
                 return result;
+            };
+
+            // Idle / umur maksimal terlewati: hapus session walau JWT-nya belum expired
+            if let Some(reason) = Self::session_timeout(session_row.get("last_update"), session_row.get("created_at")) {
+                if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1 AND token_cookie = $2"#)
+                    .bind(session.usernid)
+                    .bind(&active_token)
+                    .execute(&mut *trans)
+                    .await {
+                        result.error = Some(format!("Failed to delete cookies: {}", e));
+                        return result;
+                    };
+
+                let _ = trans.commit().await;
+                let _ = TokenRevocationService::revoke(&session);
+                result.error = Some(reason.to_string());
+                return result;
             }
 
             if update {
@@ -323,7 +848,7 @@ impl AuthService {
             let mut user_session: Claims = session.clone();
             if !cookies.is_empty() {
                 // println!("Update Session 2: {}", cookies);
-                match  sqlx::query(r#"UPDATE cookies SET token_cookie = $1, last_update = $3 WHERE user_nid = $2"#)
+                match  sqlx::query(r#"UPDATE cookies SET last_update = $3 WHERE user_nid = $2 AND token_cookie = $1"#)
                     .bind(&active_token)
                     .bind(session.usernid)
                     .bind(GenericService::get_timestamp())
@@ -344,88 +869,443 @@ impl AuthService {
                         }
                     };
             } else {
-                // println!("Check Session 2");
-                let row_option = match sqlx::query(r#"SELECT token_cookie, last_update FROM cookies WHERE user_nid = $1"#)
+                // Setiap login = baris baru, session di device lain tetap hidup
+                if let Err(e) = sqlx::query(r#"INSERT INTO cookies (user_nid, token_cookie, app_computer_name, app_ip_address, last_update, app_name, session_id, app_device, created_at) 
+                            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $5)"#)
                     .bind(session.usernid)
-                    // .bind(&active_token)
+                    .bind(&active_token)
+                    .bind(session.comp_name.clone().unwrap_or_default())
+                    .bind(session.ip_address.clone().unwrap_or_default())
+                    .bind(GenericService::get_timestamp())
+                    .bind(app_name)
+                    .bind(GenericService::random_string(32))
+                    .bind(session.user_agent.clone().unwrap_or_default())
+                    .execute(&mut *trans)
+                    .await {
+                        result.error = Some(format!("Failed to insert cookies: {}", e));
+                        return result;
+                    };
+                result.result = true;
+                user_session.app_name = Some(app_name.to_string());
+                result.message = "Login successfully".to_string();
+                result.data = Some(user_session);
+                new_session = true;
+            }
+        }
+
+        if let Err(e) = trans.commit().await {
+            result.error = Some(format!("Failed to commit transaction: {}", e));
+            return result;
+        };
+
+        // Semua jalur login (password, OTP, Google, 2FA) berakhir di sini
+        if new_session {
+            AuditService::record(SecurityEvent {
+                detail: Some(app_name.to_string()),
+                ..SecurityEvent::from_claims(SecurityEventType::LoginSuccess, &session)
+            }).await;
+        }
+
+        return result;
+    }
+
+    /// Hapus session yang sedang dipakai dan catat logout dari IP / device request
+    pub async fn logout(session: Claims, token: String, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let result = Self::check_session(session.clone(), token.clone(), token, true, false, false, app_name).await;
+
+        // Salinan token di tempat lain juga langsung tidak berlaku
+        if let Err(e) = TokenRevocationService::revoke(&session) {
+            println!("❌ Token Revocation Error: {}", e);
+        }
+
+        if result.result {
+            AuditService::record(SecurityEvent {
+                user_nid: Some(session.usernid),
+                email: Some(session.email),
+                ..SecurityEvent::from_request(SecurityEventType::Logout, req)
+            }).await;
+        }
+
+        result
+    }
+
+    /// Daftar session aktif user di semua device, `current_token` untuk menandai session yang sedang dipakai
+    pub async fn list_sessions(usernid: i32, current_token: &str) -> ActionResult<Vec<UserSession>, String> {
+        let mut result: ActionResult<Vec<UserSession>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"SELECT session_id, app_name, app_ip_address, app_device,
+                to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at,
+                to_char(last_update, 'YYYY-MM-DD HH24:MI:SS') AS last_activity,
+                token_cookie = $2 AS current
+            FROM cookies
+            WHERE user_nid = $1
+            ORDER BY last_update DESC"#)
+            .bind(usernid)
+            .bind(current_token)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => {
+                    result.result = true;
+                    result.data = Some(rows.iter().map(|row| UserSession {
+                        session_id: row.get("session_id"),
+                        app_name: row.get("app_name"),
+                        ip_address: row.get("app_ip_address"),
+                        device: row.get("app_device"),
+                        created_at: row.get("created_at"),
+                        last_activity: row.get("last_activity"),
+                        current: row.try_get::<Option<bool>, _>("current").unwrap_or_default().unwrap_or(false),
+                    }).collect());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch cookies: {}", e));
+                }
+            }
+
+        result
+    }
+
+    /// Cabut satu session milik user (termasuk refresh token-nya)
+    pub async fn revoke_session(usernid: i32, session_id: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query_scalar::<_, String>(r#"DELETE FROM cookies WHERE user_nid = $1 AND session_id = $2 RETURNING token_cookie"#)
+            .bind(usernid)
+            .bind(session_id)
+            .fetch_all(connection)
+            .await {
+                Ok(tokens) if !tokens.is_empty() => {
+                    Self::deny_tokens(&tokens);
+                    result.result = true;
+                    result.message = "Session revoked successfully".to_string();
+                }
+                Ok(_) => {
+                    result.message = "Session not found".to_string();
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to delete cookies: {}", e));
+                }
+            }
+
+        result
+    }
+
+    /// Access token dari baris `cookies` yang dihapus masuk denylist supaya salinannya ikut mati
+    fn deny_tokens(tokens: &[String]) {
+        for token in tokens {
+            if let Err(e) = TokenRevocationService::revoke_token(token) {
+                println!("❌ Token Revocation Error: {}", e);
+            }
+        }
+    }
+
+    /// Admin: cabut semua token user yang dibuat sebelum `before` (waktu Jakarta).
+    /// Access token lama ditolak lewat Redis, session yang dimulai sebelum `before` dihapus supaya refresh token-nya mati.
+    pub async fn revoke_tokens_before(usernid: i32, before: chrono::NaiveDateTime) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let before_unix = match chrono_tz::Asia::Jakarta.from_local_datetime(&before).earliest() {
+            Some(local) => local.timestamp(),
+            None => {
+                result.message = "Invalid time".to_string();
+                return result;
+            }
+        };
+
+        if let Err(e) = TokenRevocationService::revoke_user_before(usernid, before_unix) {
+            result.error = Some(format!("Redis error: {}", e));
+            return result;
+        }
+
+        match sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1 AND created_at < $2"#)
+            .bind(usernid)
+            .bind(before)
+            .execute(connection)
+            .await {
+                Ok(row) => {
+                    result.result = true;
+                    result.message = format!("Tokens issued before {} revoked, {} session(s) removed", before.format("%Y-%m-%d %H:%M:%S"), row.rows_affected());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to delete cookies: {}", e));
+                }
+            }
+
+        result
+    }
+
+    /// Cabut semua session user kecuali yang sedang dipakai
+    pub async fn revoke_other_sessions(usernid: i32, current_token: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query_scalar::<_, String>(r#"DELETE FROM cookies WHERE user_nid = $1 AND token_cookie <> $2 RETURNING token_cookie"#)
+            .bind(usernid)
+            .bind(current_token)
+            .fetch_all(connection)
+            .await {
+                Ok(tokens) => {
+                    Self::deny_tokens(&tokens);
+                    result.result = true;
+                    result.message = format!("{} other session(s) revoked", tokens.len());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to delete cookies: {}", e));
+                }
+            }
+
+        result
+    }
+
+    /// Alasan session tidak berlaku lagi karena idle / melewati umur maksimal, `None` kalau masih aktif
+    fn session_timeout(last_update: chrono::NaiveDateTime, created_at: chrono::NaiveDateTime) -> Option<&'static str> {
+        let now = GenericService::get_timestamp();
+
+        if session_idle_timeout().is_some_and(|idle| last_update + idle < now) {
+            return Some("Session expired due to inactivity");
+        }
+
+        if created_at + session_max_age() < now {
+            return Some("Session has reached its maximum age, please login again");
+        }
+
+        None
+    }
+
+    /// Umur refresh token untuk session yang dibuat pada `created_at`, dipotong supaya tidak melewati umur maksimal session
+    pub fn refresh_ttl_for(created_at: chrono::NaiveDateTime) -> chrono::Duration {
+        let remaining = created_at + session_max_age() - GenericService::get_timestamp();
+        refresh_token_ttl().min(remaining).max(chrono::Duration::zero())
+    }
+
+    /// 🧹 Hapus baris `cookies` yang sudah idle, melewati umur maksimal, atau refresh token-nya expired
+    pub async fn purge_expired_sessions() -> Result<u64, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let now = GenericService::get_timestamp();
+        // Idle dimatikan = pakai batas umur maksimal saja
+        let idle_cutoff = session_idle_timeout().map_or(now - session_max_age(), |idle| now - idle);
+
+        sqlx::query(r#"DELETE FROM cookies
+            WHERE last_update < $1
+            OR COALESCE(created_at, last_update) < $2
+            OR refresh_expires < $3"#)
+            .bind(idle_cutoff)
+            .bind(now - session_max_age())
+            .bind(now)
+            .execute(connection)
+            .await
+            .map(|done| done.rows_affected())
+            .map_err(|e| format!("Failed to delete cookies: {}", e))
+    }
+
+    /// Simpan hash refresh token baru di baris `cookies` milik access token ini.
+    /// `family` diisi saat rotasi supaya seluruh rantai token bisa dicabut sekaligus.
+    pub async fn issue_refresh_token(usernid: i32, access_token: &str, family: Option<String>) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let refresh_token = GenericService::random_string(64);
+        let refresh_expires = GenericService::get_timestamp() + Self::refresh_ttl_for(GenericService::get_timestamp());
+
+        match sqlx::query(r#"UPDATE cookies 
+            SET refresh_token_hash = $1, token_family = $2, refresh_expires = $3, rotated_refresh_hashes = '{}'
+            WHERE user_nid = $4 AND token_cookie = $5"#)
+            .bind(hash_token(&refresh_token))
+            .bind(family.unwrap_or_else(|| GenericService::random_string(32)))
+            .bind(refresh_expires)
+            .bind(usernid)
+            .bind(access_token)
+            .execute(connection)
+            .await {
+                Ok(row) if row.rows_affected() == 0 => {
+                    result.message = "Session not found".to_string();
+                }
+                Ok(_) => {
+                    result.result = true;
+                    result.data = Some(refresh_token);
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to update cookies: {}", e));
+                }
+            };
+
+        result
+    }
+
+    /// Tukar refresh token dengan access token + refresh token baru (rotasi).
+    /// Kalau refresh token lama dipakai ulang, seluruh token family dicabut.
+    pub async fn refresh_session(refresh_token: String, req: &HttpRequest, app_name: &str) -> ActionResult<SessionTokens, String> {
+        let mut result: ActionResult<SessionTokens, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let token_hash = hash_token(&refresh_token);
+
+        let mut trans = match connection.begin().await {
+            Ok(t) => t,
+            Err(e) => {
+                result.error = Some(format!("Database error: {}", e));
+                return result;
+            }            
+        };
+
+        let session_row = match sqlx::query(r#"SELECT user_nid, refresh_expires > $2 AS active,
+                last_update, COALESCE(created_at, last_update) AS created_at
+                FROM cookies WHERE refresh_token_hash = $1 FOR UPDATE"#)
+            .bind(&token_hash)
+            .bind(GenericService::get_timestamp())
+            .fetch_optional(&mut *trans)
+            .await {
+                Ok(row) => row,
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch cookies: {}", e));
+                    return result;
+                }
+            };
+
+        let (usernid, active, last_update, created_at): (i32, Option<bool>, chrono::NaiveDateTime, chrono::NaiveDateTime) = match session_row {
+            Some(row) => (row.get("user_nid"), row.get("active"), row.get("last_update"), row.get("created_at")),
+            None => {
+                // Token sudah pernah dirotasi -> kemungkinan dicuri, cabut seluruh family
+                let reused_family: Option<String> = match sqlx::query(r#"SELECT token_family FROM cookies WHERE $1 = ANY(rotated_refresh_hashes)"#)
+                    .bind(&token_hash)
                     .fetch_optional(&mut *trans)
                     .await {
-                        Ok(row) => row,
+                        Ok(row) => row.and_then(|r| r.get("token_family")),
                         Err(e) => {
                             result.error = Some(format!("Failed to fetch cookies: {}", e));
                             return result;
                         }
                     };
 
-                if let Some(row) = row_option {
-                    // println!("Check Session 3");
-                    let last_update: Option<chrono::DateTime<chrono::Utc>> = row.get("last_update");
-                    let user_token: Option<String> = row.get("token_cookie");
-                    if let Ok(decode_session) = validate_jwt(&user_token.unwrap_or_default()) {
-                        user_session = decode_session;
-                        result.data = Some(user_session);
-                    }
-
-                    // println!("Lastupdate: {}, now: {}", last_update.unwrap_or_default().to_string(), GenericService::get_timestamp().to_string());
-                    let expired_date: chrono::NaiveDateTime = last_update.unwrap_or_default().naive_utc();
-                    if expired_date > GenericService::get_timestamp() {
-                        result.message = format!(
-                            "This user ({}) with IP:{} is already logged in from another browser/machine (LastUpdate: {}), are you sure you want to kick this logged in user?",
-                            session.fullname,
-                            session.ip_address.clone().expect("IP address not found"),
-                            last_update.unwrap_or_default().to_string()
-                        );
-                        
-                        result.data = Some(session);
-                        
-                        // nanti ada buat multiple session sama nendang
-                        return result;
-                    } else {
-                        // println!("Update cookies 3");
-                        if let Err(e) = sqlx::query(r#"UPDATE cookies SET last_update = $1, token_cookie = $3 WHERE user_nid = $2"#)
-                            .bind(GenericService::get_timestamp())
-                            .bind(session.usernid)
-                            .bind(&active_token)
+                match reused_family {
+                    Some(family) => {
+                        if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE token_family = $1"#)
+                            .bind(&family)
                             .execute(&mut *trans)
                             .await {
-                                result.error = Some(format!("Failed to update cookies: {}", e));
+                                result.error = Some(format!("Failed to delete cookies: {}", e));
                                 return result;
                             };
-                        result.result = true;
-                        result.message = "Session updated successfully".to_string();
-                    }
-                } else {
-                    // println!("Insert cookies");
-                    if let Err(e) = sqlx::query(r#"INSERT INTO cookies (user_nid, token_cookie, app_computer_name, app_ip_address, last_update, app_name) 
-                                VALUES ($1, $2, $3, $4, $5, $6)"#)
-                        .bind(session.usernid)
-                        .bind(&active_token)
-                        .bind(session.comp_name.clone().unwrap_or_default())
-                        .bind(session.ip_address.clone().unwrap_or_default())
-                        .bind(GenericService::get_timestamp().checked_add_days(chrono::Days::new(2)).unwrap_or_default())
-                        .bind(app_name)
-                        .execute(&mut *trans)
-                        .await {
-                            result.error = Some(format!("Failed to insert cookies: {}", e));
+
+                        if let Err(e) = trans.commit().await {
+                            result.error = Some(format!("Failed to commit transaction: {}", e));
                             return result;
-                        };
-                    result.result = true;
-                    user_session.app_name = Some(app_name.to_string());
-                    result.message = "Login successfully".to_string();
-                    result.data = Some(user_session);
+                        }
+
+                        println!("⚠️ Refresh token reuse detected, family {} revoked", family);
+                        result.message = "Refresh token reuse detected, please login again".to_string();
+                    }
+                    None => {
+                        result.message = "Invalid refresh token".to_string();
+                    }
                 }
+                return result;
             }
+        };
+
+        let timeout = Self::session_timeout(last_update, created_at);
+
+        if !active.unwrap_or(false) || timeout.is_some() {
+            let _ = sqlx::query(r#"DELETE FROM cookies WHERE refresh_token_hash = $1"#)
+                .bind(&token_hash)
+                .execute(&mut *trans)
+                .await;
+            let _ = trans.commit().await;
+            result.message = timeout.unwrap_or("Session has expired").to_string();
+            return result;
         }
 
+        let refresh_ttl = Self::refresh_ttl_for(created_at);
+
+        let user_row = match sqlx::query(
+            r#"
+            SELECT 
+                B.autonid AS user_id, 
+                B.fullname,
+                A.email, 
+                A.disable_login, 
+                A.picture, 
+                A.register_date
+            FROM users A
+            LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+            WHERE A.web_cif_id = $1
+            "#
+        ).bind(usernid)
+        .fetch_optional(&mut *trans)
+        .await {
+            Ok(Some(row)) => row,
+            Ok(None) => {
+                result.message = "User not found".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(format!("Failed to fetch users: {}", e));
+                return result;
+            }
+        };
+
+        let mut claims = Self::claims_from_row(&user_row, req, app_name);
+        if claims.disabled_login {
+            let _ = sqlx::query(r#"DELETE FROM cookies WHERE refresh_token_hash = $1"#)
+                .bind(&token_hash)
+                .execute(&mut *trans)
+                .await;
+            let _ = trans.commit().await;
+            result.message = "Login disabled".to_string();
+            return result;
+        }
+
+        // Role bisa berubah sejak login, ambil ulang setiap refresh
+        if let Err(e) = RoleService::attach_access(&mut claims).await {
+            result.error = Some(e);
+            return result;
+        }
+
+        let (access_token, claims) = match create_jwt(claims) {
+            Ok(minted) => minted,
+            Err(e) => {
+                result.error = Some(format!("Failed to create JWT: {}", e));
+                return result;
+            }
+        };
+        let new_refresh_token = GenericService::random_string(64);
+
+        if let Err(e) = sqlx::query(r#"UPDATE cookies 
+            SET token_cookie = $1, refresh_token_hash = $2, refresh_expires = $3,
+            rotated_refresh_hashes = array_append(rotated_refresh_hashes, $4),
+            last_update = $5, app_ip_address = $6
+            WHERE refresh_token_hash = $4"#)
+            .bind(&access_token)
+            .bind(hash_token(&new_refresh_token))
+            .bind(GenericService::get_timestamp() + refresh_ttl)
+            .bind(&token_hash)
+            .bind(GenericService::get_timestamp())
+            .bind(GenericService::get_ip_address(req))
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to update cookies: {}", e));
+                return result;
+            };
+
         if let Err(e) = trans.commit().await {
             result.error = Some(format!("Failed to commit transaction: {}", e));
             return result;
-        };
+        }
 
-        return result;
+        result.result = true;
+        result.message = "Session refreshed successfully".to_string();
+        result.data = Some(SessionTokens {
+            claims,
+            access_token,
+            refresh_token: new_refresh_token,
+            refresh_ttl,
+        });
+
+        result
     }
 
-    pub async fn reset_password(request: ResetPasswordRequest) -> ActionResult<String, String> {
+    pub async fn reset_password(request: ResetPasswordRequest, req: &HttpRequest) -> ActionResult<String, String> {
         let mut result: ActionResult<String, String> = ActionResult::default();
 
         let connection = CONNECTION.get().expect("DB_POOL not initialized");
@@ -440,23 +1320,31 @@ impl AuthService {
             }            
         };
 
-        let query_result : String = match sqlx::query(r#"
+        let (query_result, web_cif_id) : (String, i32) = match sqlx::query(r#"
             UPDATE users 
             SET reset_password_key = $1,
             reset_password_flag = $2,
             reset_password_date = $3
             WHERE email = $4
-            RETURNING reset_password_key;"#)
+            RETURNING reset_password_key, web_cif_id;"#)
             .bind(GenericService::random_string(70))
             .bind(true)
             .bind(GenericService::get_timestamp())
             .bind(&request.email)
             .fetch_one(&mut *trans).await {
-                Ok(row) => row.get("reset_password_key"),
+                Ok(row) => (row.get("reset_password_key"), row.get("web_cif_id")),
                 Err(e) => {
                     // result.error = Some(format!("Failed to insert users: {}", e));
                     result.message = "User not found".to_string();
                     println!("Failed to update users: {}", e);
+
+                    AuditService::record(SecurityEvent {
+                        email: request.email.clone(),
+                        success: false,
+                        detail: Some(result.message.clone()),
+                        ..SecurityEvent::from_request(SecurityEventType::PasswordResetRequested, req)
+                    }).await;
+
                     return result;
                 }
             };
@@ -464,6 +1352,12 @@ impl AuthService {
         if query_result.is_empty() {
             result.message = "User not found".to_string();
         } else {
+            AuditService::record(SecurityEvent {
+                user_nid: Some(web_cif_id),
+                email: request.email.clone(),
+                ..SecurityEvent::from_request(SecurityEventType::PasswordResetRequested, req)
+            }).await;
+
             result.result = true;
             result.message = "Reset password successfully".to_string();
 
@@ -489,9 +1383,20 @@ impl AuthService {
         return result;
     }
 
-    pub async fn change_password(request: ChangePasswordRequest) -> ActionResult<String, String> {
+    /// Ganti password pakai reset key. Key hanya berlaku `RESET_PASSWORD_MINUTES` (default 60) dan sekali pakai,
+    /// semua session user di tabel `cookies` ikut dihapus.
+    pub async fn change_password(request: ChangePasswordRequest, req: &HttpRequest) -> ActionResult<String, String> {
         let mut result: ActionResult<String, String> = ActionResult::default();
 
+        if request.reset_password_key.is_empty() {
+            result.message = "Reset password key is invalid or has expired".to_string();
+            return result;
+        }
+
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let reset_minutes: i64 = secrets.get("RESET_PASSWORD_MINUTES").and_then(|v| v.parse().ok()).unwrap_or(60);
+        let now = GenericService::get_timestamp();
+
         let connection = CONNECTION.get().expect("DB_POOL not initialized");
         let mut trans = match connection.begin().await {
             Ok(t) => t,
@@ -501,19 +1406,30 @@ impl AuthService {
             }            
         };
 
-        let enc_password = encrypt_text(request.password.unwrap_or_default());
-        println!("{}", enc_password);
+        let enc_password = match hash_password(&request.password.unwrap_or_default()).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                result.error = Some(format!("Failed to hash password: {}", e));
+                return result;
+            }
+        };
 
+        // Key langsung dikosongkan supaya tidak bisa dipakai ulang
         let query_result = match sqlx::query(r#"
             UPDATE users 
             SET password = $1,
-            reset_password_date = $2
-            WHERE reset_password_key = $3 AND email = $4;"#)
+            reset_password_date = $2,
+            reset_password_key = NULL,
+            reset_password_flag = false
+            WHERE reset_password_key = $3 AND email = $4
+                AND reset_password_flag = true AND reset_password_date > $5
+            RETURNING web_cif_id, (SELECT fullname FROM user_kyc WHERE autonid = web_cif_id) AS fullname;"#)
             .bind(enc_password)
-            .bind(GenericService::get_timestamp())
+            .bind(now)
             .bind(request.reset_password_key.clone())
             .bind(&request.email)
-            .execute(&mut *trans).await {
+            .bind(now - chrono::Duration::minutes(reset_minutes))
+            .fetch_optional(&mut *trans).await {
                 Ok(row) => row,
                 Err(e) => {
                     result.error = Some(format!("Failed to update users: {}", e));
@@ -521,17 +1437,48 @@ impl AuthService {
                 }
             };
 
-        if query_result.rows_affected() == 1 {
-            result.result = true;
-            result.message = "Change password successfully".to_string();
-        } else {
-            result.message = "Reset password key not found".to_string();
-        }
+        let Some(row) = query_result else {
+            result.message = "Reset password key is invalid or has expired".to_string();
+            return result;
+        };
+
+        // Logout semua device
+        if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1"#)
+            .bind(row.get::<i32, _>("web_cif_id"))
+            .execute(&mut *trans)
+            .await {
+                result.error = Some(format!("Failed to delete cookies: {}", e));
+                return result;
+            }
         
         if let Err(e) = trans.commit().await {
             result.error = Some(format!("Failed to commit transaction: {}", e));
             return result;
         }
+
+        AuditService::record(SecurityEvent {
+            user_nid: Some(row.get("web_cif_id")),
+            email: request.email.clone(),
+            detail: Some("reset_password_key".to_string()),
+            ..SecurityEvent::from_request(SecurityEventType::PasswordChanged, req)
+        }).await;
+
+        let mut mail_data = HashMap::new();
+        mail_data.insert("username".to_string(), row.try_get::<Option<String>, _>("fullname").unwrap_or_default());
+        mail_data.insert("company_name".to_string(), Some("PT. TECH SNAKE SYSTEM".to_string()));
+        mail_data.insert("subject".to_string(), Some("Password Anda Telah Diubah".to_string()));
+        mail_data.insert("email".to_string(), request.email);
+        mail_data.insert("title".to_string(), Some(String::from("PASSWORD CUSTOMER ONBOARDING DIUBAH")));
+        mail_data.insert("changed_at".to_string(), Some(now.format("%d-%m-%Y %H:%M:%S WIB").to_string()));
+
+        let mail_result : ActionResult<String, String> = MailService::send(mail_data, "password-changed").await;
+        
+        if let Some(e) = mail_result.error {
+            println!("❌ Mail Error: {}", e);
+        }
+
+        result.result = true;
+        result.message = "Change password successfully".to_string();
         return result;
     }
     
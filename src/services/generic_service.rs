@@ -75,6 +75,16 @@ impl GenericService {
         return test
     }
 
+    pub fn get_user_agent(req: &HttpRequest) -> String {
+        req.headers()
+            .get("User-Agent")
+            .and_then(|ua| ua.to_str().ok())
+            .map_or_else(
+                || "Unknown Device".to_string(),
+                |ua| ua.chars().take(255).collect(),
+            )
+    }
+
     pub fn is_localhost_origin(req: &HttpRequest) -> bool {
         if let Some(origin) = req.headers().get("Origin") {
             if let Ok(origin_str) = origin.to_str() {
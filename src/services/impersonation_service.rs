@@ -0,0 +1,134 @@
+use actix_web::HttpRequest;
+
+use crate::{middleware::{jwt_session::{create_jwt_with_ttl, Claims}, model::ActionResult}, services::{audit_service::{AuditService, SecurityEvent, SecurityEventType}, auth_service::AuthService, generic_service::GenericService, role_service::RoleService, token_revocation_service::TokenRevocationService}, CONNECTION, SECRETS};
+
+/// `app_name` baris `cookies` impersonation, supaya terlihat jelas di daftar session user
+pub const IMPERSONATION_APP: &str = "snakesystem-impersonation";
+
+/// 🕵️ Impersonation admin untuk support: token bearer read-only atas nama user, tanpa refresh token.
+///
+/// Token berlaku `IMPERSONATION_MINUTES` (default 30), `impersonator` di claims berisi ID admin.
+/// Request tulis ditolak di [`crate::middleware::auth_extractor::authenticate`], event audit selama impersonation ditandai `actor_nid`.
+pub struct ImpersonationService;
+
+impl ImpersonationService {
+
+    fn ttl() -> chrono::Duration {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let minutes = secrets.get("IMPERSONATION_MINUTES").and_then(|v| v.parse().ok()).unwrap_or(30);
+        chrono::Duration::minutes(minutes)
+    }
+
+    pub async fn start(admin: &Claims, usernid: i32, reason: &str, req: &HttpRequest) -> ActionResult<(String, Claims), String> {
+        let mut result: ActionResult<(String, Claims), String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        if admin.impersonator.is_some() {
+            result.message = "Stop the current impersonation first".to_string();
+            return result;
+        }
+
+        if admin.usernid == usernid {
+            result.message = "Cannot impersonate yourself".to_string();
+            return result;
+        }
+
+        let mut user = match AuthService::claims_for_user(usernid, req, IMPERSONATION_APP).await {
+            ActionResult { data: Some(user), .. } => user,
+            response => {
+                result.error = response.error;
+                result.message = response.message;
+                return result;
+            }
+        };
+
+        if let Err(e) = RoleService::attach_access(&mut user).await {
+            result.error = Some(e);
+            return result;
+        }
+
+        // Impersonate admin lain = eskalasi akses (mis. baca audit log), jadi hanya user tanpa permission
+        if !user.permissions.is_empty() {
+            result.message = "Cannot impersonate a user with administrative permissions".to_string();
+            return result;
+        }
+
+        user.impersonator = Some(admin.usernid);
+
+        let ttl = Self::ttl();
+        let (token, user) = match create_jwt_with_ttl(user, ttl) {
+            Ok(minted) => minted,
+            Err(e) => {
+                result.error = Some(format!("Failed to create JWT: {}", e));
+                return result;
+            }
+        };
+
+        // Baris `cookies` dibuat langsung (bukan lewat check_session) supaya tidak tercatat sebagai login user
+        if let Err(e) = sqlx::query(r#"INSERT INTO cookies (user_nid, token_cookie, app_computer_name, app_ip_address, last_update, app_name, session_id, app_device, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $5)"#)
+            .bind(usernid)
+            .bind(&token)
+            .bind(user.comp_name.clone().unwrap_or_default())
+            .bind(user.ip_address.clone().unwrap_or_default())
+            .bind(GenericService::get_timestamp())
+            .bind(IMPERSONATION_APP)
+            .bind(GenericService::random_string(32))
+            .bind(user.user_agent.clone().unwrap_or_default())
+            .execute(connection)
+            .await {
+                result.error = Some(format!("Failed to insert cookies: {}", e));
+                return result;
+            }
+
+        AuditService::record(SecurityEvent {
+            detail: Some(format!("by {} ({}): {}", admin.email, admin.usernid, reason.trim())),
+            ..SecurityEvent::from_claims(SecurityEventType::ImpersonationStarted, &user)
+        }).await;
+
+        result.result = true;
+        result.message = format!("Impersonating {} for {} minutes, read-only", user.email, ttl.num_minutes());
+        result.data = Some((token, user));
+        result
+    }
+
+    /// Akhiri impersonation: hapus baris `cookies` dan masukkan token ke denylist
+    pub async fn stop(session: &Claims, token: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        if session.impersonator.is_none() {
+            result.message = "Not an impersonation session".to_string();
+            return result;
+        }
+
+        if let Err(e) = sqlx::query(r#"DELETE FROM cookies WHERE user_nid = $1 AND token_cookie = $2"#)
+            .bind(session.usernid)
+            .bind(token)
+            .execute(connection)
+            .await {
+                result.error = Some(format!("Failed to delete cookies: {}", e));
+                return result;
+            }
+
+        if let Err(e) = TokenRevocationService::revoke(session) {
+            result.error = Some(format!("Redis error: {}", e));
+            return result;
+        }
+
+        AuditService::record(SecurityEvent::from_claims(SecurityEventType::ImpersonationStopped, session)).await;
+
+        result.result = true;
+        result.message = "Impersonation stopped".to_string();
+        result
+    }
+
+    /// Catat request tulis yang ditolak selama impersonation
+    pub async fn record_blocked_write(session: &Claims, req: &HttpRequest) {
+        AuditService::record(SecurityEvent {
+            success: false,
+            detail: Some(format!("{} {}", req.method(), req.path())),
+            ..SecurityEvent::from_claims(SecurityEventType::ImpersonationWriteBlocked, session)
+        }).await;
+    }
+}
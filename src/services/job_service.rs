@@ -0,0 +1,59 @@
+use crate::{services::{account_service::AccountService, auth_service::AuthService, kyc_service::KycService}, SECRETS};
+
+/// ⏱️ Pekerjaan berkala yang jalan di background selama service hidup
+pub struct JobService;
+
+impl JobService {
+
+    /// Jalankan semua job, dipanggil sekali dari `main` setelah `CONNECTION` & `SECRETS` siap
+    pub fn start() {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let interval_secs: u64 = secrets.get("ACCOUNT_PURGE_INTERVAL_SECONDS").and_then(|v| v.parse().ok()).unwrap_or(3600);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match AccountService::purge_deleted_accounts().await {
+                    Ok(0) => {}
+                    Ok(purged) => println!("🧹 {} deleted account(s) anonymized", purged),
+                    Err(e) => eprintln!("❌ Account purge error: {}", e),
+                }
+            }
+        });
+
+        let session_interval_secs: u64 = secrets.get("SESSION_PURGE_INTERVAL_SECONDS").and_then(|v| v.parse().ok()).unwrap_or(900);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(session_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match AuthService::purge_expired_sessions().await {
+                    Ok(0) => {}
+                    Ok(purged) => println!("🧹 {} expired session(s) purged", purged),
+                    Err(e) => eprintln!("❌ Session purge error: {}", e),
+                }
+            }
+        });
+
+        let kyc_interval_secs: u64 = secrets.get("KYC_ENCRYPT_INTERVAL_SECONDS").and_then(|v| v.parse().ok()).unwrap_or(300);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(kyc_interval_secs));
+
+            loop {
+                interval.tick().await;
+
+                match KycService::seal_pending().await {
+                    Ok(0) => {}
+                    Ok(sealed) => println!("🔐 {} user_kyc row(s) encrypted", sealed),
+                    Err(e) => eprintln!("❌ KYC encryption error: {}", e),
+                }
+            }
+        });
+    }
+}
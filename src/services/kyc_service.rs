@@ -0,0 +1,176 @@
+use sqlx::{postgres::PgRow, PgConnection, Row};
+
+use crate::{middleware::crypto::{active_key_id, is_sealed, open, seal, Redacted}, CONNECTION};
+
+/// Kolom `user_kyc` yang disimpan terenkripsi
+pub const ENCRYPTED_FIELDS: [&str; 8] = [
+    "idcard_number",
+    "npwp_number",
+    "bank_account_number",
+    "mother_name",
+    "idcard_file",
+    "selfie_file",
+    "signature_file",
+    "npwp_file",
+];
+
+/// 🪪 Enkripsi field-level data KYC (AES-256-GCM, envelope `v2` dari [`crate::middleware::crypto::seal`]).
+///
+/// Context enkripsi = `user_kyc.<kolom>:<autonid>`, jadi ciphertext yang disalin ke kolom / user lain gagal didekripsi.
+/// Data plaintext (data lama, atau ditulis langsung oleh aplikasi onboarding) tetap terbaca, dienkripsi saat pertama kali terbaca
+/// lewat API ([`KycService::seal_user`]) dan oleh [`KycService::seal_pending`] yang jalan berkala.
+pub struct KycService;
+
+impl KycService {
+
+    fn context(field: &str, autonid: i32) -> String {
+        format!("user_kyc.{}:{}", field, autonid)
+    }
+
+    /// 🔓 Dekripsi kolom terenkripsi di hasil `row_to_json` milik `autonid`, yang gagal didekripsi jadi `null`.
+    /// Return `true` kalau masih ada kolom plaintext, supaya pemanggil bisa langsung [`KycService::seal_user`]
+    pub fn open_fields(autonid: i32, data: &mut serde_json::Map<String, serde_json::Value>) -> bool {
+        let mut has_plaintext = false;
+
+        for field in ENCRYPTED_FIELDS {
+            let Some(serde_json::Value::String(value)) = data.get(field) else { continue };
+
+            if !is_sealed(value) {
+                has_plaintext |= !value.is_empty();
+                continue;
+            }
+
+            let opened = match open(value, &Self::context(field, autonid)) {
+                Ok(plain) => serde_json::Value::String(plain.into_inner()),
+                Err(e) => {
+                    eprintln!("❌ Failed to decrypt user_kyc.{} for {}: {}", field, autonid, e);
+                    serde_json::Value::Null
+                }
+            };
+
+            data.insert(field.to_string(), opened);
+        }
+
+        has_plaintext
+    }
+
+    /// 🙈 Samarkan kolom terenkripsi (mis. selama impersonation): nomor hanya 4 karakter terakhir, path file disembunyikan
+    pub fn mask_fields(data: &mut serde_json::Map<String, serde_json::Value>) {
+        for field in ENCRYPTED_FIELDS {
+            let Some(serde_json::Value::String(value)) = data.get(field) else { continue };
+
+            let masked = if field.ends_with("_file") || field == "mother_name" {
+                "********".to_string()
+            } else {
+                let visible: String = value.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+                format!("****{}", visible)
+            };
+
+            data.insert(field.to_string(), serde_json::Value::String(masked));
+        }
+    }
+
+    fn select_fields() -> String {
+        format!("SELECT autonid, {} FROM user_kyc", ENCRYPTED_FIELDS.join(", "))
+    }
+
+    /// Enkripsi kolom plaintext dan re-enkripsi envelope yang tidak diawali `current_prefix` di satu baris hasil [`KycService::select_fields`]
+    async fn seal_row(conn: &mut PgConnection, row: &PgRow, current_prefix: &str) -> Result<(), String> {
+        let autonid: i32 = row.get("autonid");
+
+        for field in ENCRYPTED_FIELDS {
+            let Some(value) = row.get::<Option<String>, _>(field) else { continue };
+
+            if value.is_empty() || value.starts_with(current_prefix) {
+                continue;
+            }
+
+            let context = Self::context(field, autonid);
+            let plain = if is_sealed(&value) {
+                match open(&value, &context) {
+                    Ok(plain) => plain,
+                    Err(e) => {
+                        eprintln!("❌ Failed to decrypt user_kyc.{} for {}: {}", field, autonid, e);
+                        continue;
+                    }
+                }
+            } else {
+                Redacted::new(value)
+            };
+
+            // Nama kolom dari ENCRYPTED_FIELDS (konstanta), bukan input user
+            sqlx::query(&format!("UPDATE user_kyc SET {} = $1 WHERE autonid = $2", field))
+                .bind(seal(plain.expose(), &context)?)
+                .bind(autonid)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| format!("Failed to update user_kyc: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// 🔐 Enkripsi data KYC satu user sekarang juga, dipanggil saat API membaca kolom yang masih plaintext
+    pub async fn seal_user(autonid: i32) -> Result<(), String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let current_prefix = format!("v2.{}.", active_key_id()?);
+
+        let mut trans = connection.begin().await.map_err(|e| format!("Database error: {}", e))?;
+
+        let row = sqlx::query(&format!("{} WHERE autonid = $1 FOR UPDATE", Self::select_fields()))
+            .bind(autonid)
+            .fetch_optional(&mut *trans)
+            .await
+            .map_err(|e| format!("Failed to fetch user_kyc: {}", e))?;
+
+        if let Some(row) = row {
+            Self::seal_row(&mut trans, &row, &current_prefix).await?;
+        }
+
+        trans.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))
+    }
+
+    /// 🔐 Enkripsi plaintext lama dan re-enkripsi envelope dengan key id lama ke `CRYPTO_ACTIVE_KEY`.
+    /// Diproses per 100 baris (`FOR UPDATE SKIP LOCKED`, aman dijalankan beberapa instance), return jumlah baris yang diubah.
+    pub async fn seal_pending() -> Result<u64, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let current_prefix = format!("v2.{}.", active_key_id()?);
+        let pending = ENCRYPTED_FIELDS
+            .iter()
+            .map(|field| format!("({0} <> '' AND {0} NOT LIKE $2)", field))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let select = format!(r#"{}
+            WHERE autonid > $1 AND ({})
+            ORDER BY autonid
+            LIMIT 100
+            FOR UPDATE SKIP LOCKED"#, Self::select_fields(), pending);
+
+        let mut last_autonid = 0i32;
+        let mut sealed_rows = 0u64;
+
+        loop {
+            let mut trans = connection.begin().await.map_err(|e| format!("Database error: {}", e))?;
+
+            let rows = sqlx::query(&select)
+                .bind(last_autonid)
+                .bind(format!("{}%", current_prefix))
+                .fetch_all(&mut *trans)
+                .await
+                .map_err(|e| format!("Failed to fetch user_kyc: {}", e))?;
+
+            if rows.is_empty() {
+                return Ok(sealed_rows);
+            }
+
+            for row in &rows {
+                last_autonid = row.get("autonid");
+                Self::seal_row(&mut trans, row, &current_prefix).await?;
+                sealed_rows += 1;
+            }
+
+            trans.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        }
+    }
+}
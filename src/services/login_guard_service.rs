@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::{middleware::model::ActionResult, services::mail_service::MailService, CONNECTION, REDIS_CLIENT, SECRETS};
+
+/// Alasan login ditolak sebelum password dicek
+#[derive(Debug)]
+pub enum LoginBlocked {
+    /// Akun dikunci sementara setelah terlalu banyak gagal
+    Locked { retry_after: u64 },
+    /// Terlalu cepat mencoba lagi (delay progresif) atau IP melewati batas
+    Throttled { retry_after: u64 },
+}
+
+/// Cek kunci / jeda lalu reservasi satu percobaan secara atomik, supaya request paralel tidak lolos bersamaan.
+/// KEYS: lock, delay, fail email, fail IP. ARGV: window, maksimal per IP, delay dasar, delay maksimal.
+/// Return `{0, percobaan_ke}` kalau boleh lanjut, `{1, ttl}` terkunci, `{2, ttl}` harus menunggu.
+const RESERVE_SCRIPT: &str = r#"
+local lock_ttl = redis.call('TTL', KEYS[1])
+if lock_ttl > 0 then return {1, lock_ttl} end
+
+local ip_failures = tonumber(redis.call('GET', KEYS[4]) or '0')
+if ip_failures >= tonumber(ARGV[2]) then return {2, math.max(redis.call('TTL', KEYS[4]), 1)} end
+
+local delay_ttl = redis.call('TTL', KEYS[2])
+if delay_ttl > 0 then return {2, delay_ttl} end
+
+local attempt = redis.call('INCR', KEYS[3])
+redis.call('EXPIRE', KEYS[3], ARGV[1])
+redis.call('INCR', KEYS[4])
+redis.call('EXPIRE', KEYS[4], ARGV[1])
+
+local delay = math.floor(math.min(tonumber(ARGV[3]) * 2 ^ math.min(attempt - 1, 16), tonumber(ARGV[4])))
+if delay > 0 then redis.call('SET', KEYS[2], 1, 'EX', delay) end
+
+return {0, attempt}
+"#;
+
+/// 🛡️ Proteksi brute-force untuk `/auth/login` dan langkah 2FA.
+///
+/// Setiap percobaan direservasi dulu di Redis sebelum password / kode dicek ([`LoginGuardService::reserve`]):
+/// counter per email (`login:fail:email:<email>`) dan per IP (`login:fail:ip:<ip>`) naik selama `LOGIN_ATTEMPT_WINDOW_SECONDS`
+/// (default 900) dan percobaan berikutnya harus menunggu `LOGIN_DELAY_BASE_SECONDS` x 2^(n-1), maksimal `LOGIN_DELAY_MAX_SECONDS`.
+/// Login berhasil menghapus counter email. Setelah `LOGIN_MAX_ATTEMPTS` (default 5) kali gagal akun dikunci
+/// `LOGIN_LOCK_SECONDS` (default 900) dan pemilik akun dikirimi email. Satu IP maksimal `LOGIN_MAX_ATTEMPTS_PER_IP` (default 20) kali gagal per window.
+pub struct LoginGuardService;
+
+impl LoginGuardService {
+
+    fn setting(key: &str, default: u64) -> u64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    fn normalize(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+
+    /// Reservasi satu percobaan sebelum verifikasi, return nomor percobaan untuk [`LoginGuardService::record_failure`]
+    pub fn reserve(email: &str, ip_address: &str) -> Result<Result<u64, LoginBlocked>, redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let email = Self::normalize(email);
+
+        let (status, value): (u8, u64) = redis::Script::new(RESERVE_SCRIPT)
+            .key(format!("login:lock:{}", email))
+            .key(format!("login:delay:{}", email))
+            .key(format!("login:fail:email:{}", email))
+            .key(format!("login:fail:ip:{}", ip_address))
+            .arg(Self::setting("LOGIN_ATTEMPT_WINDOW_SECONDS", 900))
+            .arg(Self::setting("LOGIN_MAX_ATTEMPTS_PER_IP", 20))
+            .arg(Self::setting("LOGIN_DELAY_BASE_SECONDS", 1))
+            .arg(Self::setting("LOGIN_DELAY_MAX_SECONDS", 30))
+            .invoke(&mut conn)?;
+
+        Ok(match status {
+            0 => Ok(value),
+            1 => Err(LoginBlocked::Locked { retry_after: value }),
+            _ => Err(LoginBlocked::Throttled { retry_after: value }),
+        })
+    }
+
+    /// Percobaan yang sudah direservasi ternyata gagal, kunci akun kalau sudah melewati batas
+    pub async fn record_failure(email: &str, ip_address: &str, attempt: u64) -> Result<(), redis::RedisError> {
+        let max_attempts = Self::setting("LOGIN_MAX_ATTEMPTS", 5);
+
+        if attempt < max_attempts {
+            return Ok(());
+        }
+
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let email = Self::normalize(email);
+        let lock_seconds = Self::setting("LOGIN_LOCK_SECONDS", 900);
+
+        redis::pipe()
+            .atomic()
+            .set_ex(format!("login:lock:{}", email), ip_address, lock_seconds).ignore()
+            .del(format!("login:fail:email:{}", email)).ignore()
+            .del(format!("login:delay:{}", email)).ignore()
+            .query::<()>(&mut conn)?;
+
+        Self::notify_locked(&email, ip_address, attempt, lock_seconds).await;
+        Ok(())
+    }
+
+    /// Login berhasil: reset counter email, percobaan ini tidak dihitung sebagai gagal untuk IP
+    pub fn record_success(email: &str, ip_address: &str) -> Result<(), redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let email = Self::normalize(email);
+
+        redis::pipe()
+            .atomic()
+            .del(&[format!("login:fail:email:{}", email), format!("login:delay:{}", email)]).ignore()
+            .decr(format!("login:fail:ip:{}", ip_address), 1).ignore()
+            .query(&mut conn)
+    }
+
+    /// Password benar tapi masih menunggu 2FA: hapus jeda saja, counter gagal tetap sampai 2FA lolos
+    pub fn release_delay(email: &str) -> Result<(), redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        redis::cmd("DEL")
+            .arg(format!("login:delay:{}", Self::normalize(email)))
+            .query(&mut conn)
+    }
+
+    async fn notify_locked(email: &str, ip_address: &str, failures: u64, lock_seconds: u64) {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        // Hanya kirim email kalau akunnya memang ada
+        let user = match sqlx::query(r#"SELECT B.fullname, A.email FROM users A
+                LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+                WHERE lower(A.email) = $1"#)
+            .bind(email)
+            .fetch_optional(connection)
+            .await {
+                Ok(Some(row)) => row,
+                Ok(None) => return,
+                Err(e) => {
+                    println!("❌ Lockout Notification Error: {}", e);
+                    return;
+                }
+            };
+
+        let mut mail_data = HashMap::new();
+        mail_data.insert("username".to_string(), user.try_get::<Option<String>, _>("fullname").unwrap_or_default());
+        mail_data.insert("company_name".to_string(), Some("PT. TECH SNAKE SYSTEM".to_string()));
+        mail_data.insert("subject".to_string(), Some("Akun Anda Dikunci Sementara".to_string()));
+        mail_data.insert("email".to_string(), Some(user.get::<String, _>("email")));
+        mail_data.insert("title".to_string(), Some("PERINGATAN KEAMANAN AKUN".to_string()));
+        mail_data.insert("failed_attempts".to_string(), Some(failures.to_string()));
+        mail_data.insert("lock_minutes".to_string(), Some((lock_seconds / 60).max(1).to_string()));
+        mail_data.insert("ip_address".to_string(), Some(ip_address.to_string()));
+
+        let mail_result: ActionResult<String, String> = MailService::send(mail_data, "account-locked").await;
+
+        if let Some(e) = mail_result.error {
+            println!("❌ Mail Error: {}", e);
+        }
+    }
+}
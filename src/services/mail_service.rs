@@ -22,6 +22,14 @@ impl MailService {
         let template_str = match template {
             "activation" => include_str!("../../templates/activation.hbs"),
             "reset-password" => include_str!("../../templates/reset_password.hbs"),
+            "otp-login" => include_str!("../../templates/otp_login.hbs"),
+            "account-locked" => include_str!("../../templates/account_locked.hbs"),
+            "password-changed" => include_str!("../../templates/password_changed.hbs"),
+            "email-change-confirm" => include_str!("../../templates/email_change_confirm.hbs"),
+            "email-change-notice" => include_str!("../../templates/email_change_notice.hbs"),
+            "account-deletion-confirm" => include_str!("../../templates/account_deletion_confirm.hbs"),
+            "account-deletion-scheduled" => include_str!("../../templates/account_deletion_scheduled.hbs"),
+            "account-confirm-code" => include_str!("../../templates/account_confirm_code.hbs"),
             _ => panic!("Template not found"),
         };
 
@@ -78,6 +86,14 @@ impl MailService {
         let template_str = match template {
             "activation" => include_str!("../../templates/activation.hbs"),
             "reset-password" => include_str!("../../templates/reset_password.hbs"),
+            "otp-login" => include_str!("../../templates/otp_login.hbs"),
+            "account-locked" => include_str!("../../templates/account_locked.hbs"),
+            "password-changed" => include_str!("../../templates/password_changed.hbs"),
+            "email-change-confirm" => include_str!("../../templates/email_change_confirm.hbs"),
+            "email-change-notice" => include_str!("../../templates/email_change_notice.hbs"),
+            "account-deletion-confirm" => include_str!("../../templates/account_deletion_confirm.hbs"),
+            "account-deletion-scheduled" => include_str!("../../templates/account_deletion_scheduled.hbs"),
+            "account-confirm-code" => include_str!("../../templates/account_confirm_code.hbs"),
             _ => return Err("Template not found".to_string()),
         };
 
@@ -0,0 +1,349 @@
+use actix_web::HttpRequest;
+use redis::Commands;
+use sqlx::Row;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{middleware::{crypto::{hash_token, open_text, seal_text}, jwt_session::Claims, model::ActionResult}, services::{audit_service::{AuditService, SecurityEvent, SecurityEventType}, generic_service::GenericService}, CONNECTION, REDIS_CLIENT, SECRETS};
+
+/// Umur challenge login 2FA di Redis (detik)
+pub const CHALLENGE_TTL: u64 = 300;
+/// Maksimal percobaan kode per challenge
+const CHALLENGE_MAX_ATTEMPTS: i64 = 5;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub struct MfaService;
+
+impl MfaService {
+
+    fn build_totp(secret: Vec<u8>, email: &str) -> Result<TOTP, String> {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let issuer = secrets.get("TOTP_ISSUER").unwrap_or_else(|| "Snakesystem".to_string());
+
+        TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, Some(issuer), email.to_string())
+            .map_err(|e| format!("Invalid TOTP config: {}", e))
+    }
+
+    /// Ambil secret TOTP user (sudah didekripsi) beserta status enabled
+    async fn load_secret(usernid: i32) -> Result<Option<(Vec<u8>, bool)>, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let row = sqlx::query(r#"SELECT totp_secret, totp_enabled FROM users WHERE web_cif_id = $1"#)
+            .bind(usernid)
+            .fetch_optional(connection)
+            .await
+            .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+        let Some(row) = row else { return Ok(None) };
+        let sealed: Option<String> = row.get("totp_secret");
+        let enabled: bool = row.try_get::<Option<bool>, _>("totp_enabled").unwrap_or_default().unwrap_or(false);
+
+        match sealed.as_deref().and_then(open_text) {
+            Some(encoded) => {
+                let secret = Secret::Encoded(encoded).to_bytes().map_err(|e| format!("Invalid TOTP secret: {:?}", e))?;
+                Ok(Some((secret, enabled)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Cek kode TOTP + tolak kode yang sama dipakai dua kali dalam window yang sama
+    fn check_code(totp: &TOTP, usernid: i32, code: &str) -> bool {
+        if !totp.check_current(code.trim()).unwrap_or(false) {
+            return false;
+        }
+
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let used_key = format!("mfa:used:{}:{}", usernid, code.trim());
+        // SET NX: kalau key sudah ada berarti kode ini barusan dipakai
+        let fresh: bool = redis::cmd("SET")
+            .arg(&used_key)
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(totp.step * 3)
+            .query::<Option<String>>(&mut conn)
+            .map(|r| r.is_some())
+            .unwrap_or(false);
+
+        fresh
+    }
+
+    /// Pakai recovery code (sekali pakai), return true kalau valid
+    async fn consume_recovery_code(usernid: i32, code: &str) -> Result<bool, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let code_hash = hash_token(&code.trim().to_uppercase());
+
+        let row = sqlx::query(r#"UPDATE users
+            SET totp_recovery_codes = array_remove(totp_recovery_codes, $2)
+            WHERE web_cif_id = $1 AND $2 = ANY(totp_recovery_codes)"#)
+            .bind(usernid)
+            .bind(code_hash)
+            .execute(connection)
+            .await
+            .map_err(|e| format!("Failed to update users: {}", e))?;
+
+        Ok(row.rows_affected() == 1)
+    }
+
+    pub async fn is_enabled(usernid: i32) -> Result<bool, String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let row = sqlx::query(r#"SELECT totp_enabled FROM users WHERE web_cif_id = $1"#)
+            .bind(usernid)
+            .fetch_optional(connection)
+            .await
+            .map_err(|e| format!("Failed to fetch users: {}", e))?;
+
+        Ok(row.and_then(|r| r.try_get::<Option<bool>, _>("totp_enabled").ok().flatten()).unwrap_or(false))
+    }
+
+    /// Generate secret baru (belum aktif sampai dikonfirmasi dengan kode pertama)
+    pub async fn setup(session: Claims) -> ActionResult<serde_json::Value, String> {
+        let mut result: ActionResult<serde_json::Value, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match Self::is_enabled(session.usernid).await {
+            Ok(true) => {
+                result.message = "Two-factor authentication is already enabled".to_string();
+                return result;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        }
+
+        let secret = Secret::generate_secret();
+        let encoded = secret.to_encoded().to_string();
+        let totp = match secret.to_bytes().map_err(|e| format!("{:?}", e)).and_then(|bytes| Self::build_totp(bytes, &session.email)) {
+            Ok(totp) => totp,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        let sealed = match seal_text(&encoded) {
+            Ok(sealed) => sealed,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        if let Err(e) = sqlx::query(r#"UPDATE users SET totp_secret = $1, totp_enabled = false WHERE web_cif_id = $2"#)
+            .bind(sealed)
+            .bind(session.usernid)
+            .execute(connection)
+            .await {
+                result.error = Some(format!("Failed to update users: {}", e));
+                return result;
+            };
+
+        result.result = true;
+        result.message = "Scan the QR code with your authenticator app, then confirm with the first code".to_string();
+        result.data = Some(serde_json::json!({
+            "secret": totp.get_secret_base32(),
+            "otpauth_url": totp.get_url(),
+        }));
+
+        result
+    }
+
+    /// Aktifkan 2FA setelah user memasukkan kode pertama, sekaligus generate recovery code
+    pub async fn confirm(session: Claims, code: &str) -> ActionResult<Vec<String>, String> {
+        let mut result: ActionResult<Vec<String>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let (secret, enabled) = match Self::load_secret(session.usernid).await {
+            Ok(Some(found)) => found,
+            Ok(None) => {
+                result.message = "Two-factor setup not started".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        if enabled {
+            result.message = "Two-factor authentication is already enabled".to_string();
+            return result;
+        }
+
+        let totp = match Self::build_totp(secret, &session.email) {
+            Ok(totp) => totp,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+
+        if !Self::check_code(&totp, session.usernid, code) {
+            result.message = "Invalid authentication code".to_string();
+            return result;
+        }
+
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let raw = GenericService::random_string(10);
+                format!("{}-{}", &raw[..5], &raw[5..])
+            })
+            .collect();
+        let hashed: Vec<String> = recovery_codes.iter().map(|c| hash_token(c)).collect();
+
+        if let Err(e) = sqlx::query(r#"UPDATE users
+            SET totp_enabled = true, totp_enabled_at = $1, totp_recovery_codes = $2
+            WHERE web_cif_id = $3"#)
+            .bind(GenericService::get_timestamp())
+            .bind(&hashed)
+            .bind(session.usernid)
+            .execute(connection)
+            .await {
+                result.error = Some(format!("Failed to update users: {}", e));
+                return result;
+            };
+
+        result.result = true;
+        result.message = "Two-factor authentication enabled, store the recovery codes in a safe place".to_string();
+        result.data = Some(recovery_codes);
+        result
+    }
+
+    /// Verifikasi kode TOTP atau recovery code milik user
+    pub async fn verify_code(usernid: i32, email: &str, code: &str) -> Result<bool, String> {
+        let (secret, enabled) = match Self::load_secret(usernid).await? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+
+        if !enabled {
+            return Ok(false);
+        }
+
+        let totp = Self::build_totp(secret, email)?;
+        if Self::check_code(&totp, usernid, code) {
+            return Ok(true);
+        }
+
+        Self::consume_recovery_code(usernid, code).await
+    }
+
+    pub async fn disable(session: Claims, code: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match Self::verify_code(session.usernid, &session.email, code).await {
+            Ok(true) => {}
+            Ok(false) => {
+                result.message = "Invalid authentication code".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        }
+
+        if let Err(e) = sqlx::query(r#"UPDATE users
+            SET totp_enabled = false, totp_secret = NULL, totp_enabled_at = NULL, totp_recovery_codes = '{}'
+            WHERE web_cif_id = $1"#)
+            .bind(session.usernid)
+            .execute(connection)
+            .await {
+                result.error = Some(format!("Failed to update users: {}", e));
+                return result;
+            };
+
+        result.result = true;
+        result.message = "Two-factor authentication disabled".to_string();
+        result
+    }
+
+    /// Simpan user yang sudah lolos password di Redis, return token challenge untuk `/auth/2fa/verify`
+    pub fn create_challenge(user: &Claims) -> Result<String, redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let token = GenericService::random_string(48);
+
+        conn.set_ex::<_, _, ()>(
+            format!("mfa:challenge:{}", hash_token(&token)),
+            serde_json::to_string(user).expect("Failed to serialize claims"),
+            CHALLENGE_TTL,
+        )?;
+
+        Ok(token)
+    }
+
+    /// User yang menunggu langkah 2FA untuk challenge ini (belum diverifikasi)
+    pub fn load_challenge(token: &str) -> ActionResult<Claims, String> {
+        let mut result: ActionResult<Claims, String> = ActionResult::default();
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        match conn.get::<_, Option<String>>(format!("mfa:challenge:{}", hash_token(token))) {
+            Ok(Some(payload)) => match serde_json::from_str(&payload) {
+                Ok(user) => {
+                    result.result = true;
+                    result.data = Some(user);
+                }
+                Err(e) => result.error = Some(format!("Invalid challenge payload: {}", e)),
+            },
+            Ok(None) => result.message = "Two-factor challenge has expired, please login again".to_string(),
+            Err(e) => result.error = Some(format!("Redis error: {}", e)),
+        }
+
+        result
+    }
+
+    /// Selesaikan challenge login (hasil [`MfaService::load_challenge`]) dengan kode TOTP / recovery code,
+    /// kode yang salah dicatat di `security_events` seperti login password
+    pub async fn verify_challenge(token: &str, user: Claims, code: &str, req: &HttpRequest) -> ActionResult<Claims, String> {
+        let (usernid, email) = (user.usernid, user.email.clone());
+        let result = Self::finish_challenge(token, user, code).await;
+
+        if result.data.is_none() {
+            AuditService::record(SecurityEvent {
+                user_nid: Some(usernid),
+                email: Some(email),
+                success: false,
+                detail: Some(format!("2fa: {}", result.error.clone().unwrap_or_else(|| result.message.clone()))),
+                ..SecurityEvent::from_request(SecurityEventType::LoginFailed, req)
+            }).await;
+        }
+
+        result
+    }
+
+    async fn finish_challenge(token: &str, user: Claims, code: &str) -> ActionResult<Claims, String> {
+        let mut result: ActionResult<Claims, String> = ActionResult::default();
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        let challenge_key = format!("mfa:challenge:{}", hash_token(token));
+        let attempts_key = format!("mfa:attempts:{}", hash_token(token));
+
+        match Self::verify_code(user.usernid, &user.email, code).await {
+            Ok(true) => {
+                let _ = conn.del::<_, ()>(&[&challenge_key, &attempts_key]);
+                result.result = true;
+                result.data = Some(user);
+            }
+            Ok(false) => {
+                let attempts: i64 = conn.incr(&attempts_key, 1).unwrap_or(CHALLENGE_MAX_ATTEMPTS);
+                let _ = conn.expire::<_, ()>(&attempts_key, CHALLENGE_TTL as i64);
+
+                if attempts >= CHALLENGE_MAX_ATTEMPTS {
+                    let _ = conn.del::<_, ()>(&[&challenge_key, &attempts_key]);
+                    result.message = "Too many invalid codes, please login again".to_string();
+                } else {
+                    result.message = "Invalid authentication code".to_string();
+                }
+            }
+            Err(e) => {
+                result.error = Some(e);
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,609 @@
+use std::time::Duration;
+
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use oauth2::{basic::BasicClient, AuthUrl, ClientId, CsrfToken, PkceCodeChallenge, RedirectUrl};
+use once_cell::sync::OnceCell;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+
+use crate::{middleware::{model::{ExternalIdentity, OidcProviderInfo}, oauth_state::{OAuthStateStore, PendingAuthorization}}, services::generic_service::GenericService, REDIS_CLIENT, SECRETS};
+
+/// Satu provider login eksternal dari secrets.
+///
+/// Provider OIDC cukup `OIDC_<NAME>_ISSUER`, endpoint diambil dari discovery document.
+/// Provider OAuth2 biasa (GitHub) tanpa issuer wajib mengisi `OIDC_<NAME>_AUTH_URL`, `_TOKEN_URL`, `_USERINFO_URL`.
+#[derive(Debug, Clone)]
+pub struct OidcProvider {
+    pub name: String,
+    pub display_name: String,
+    pub issuer: Option<String>,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scopes: Vec<String>,
+    pub redirect_url: String,
+    /// Anggap email dari provider sudah terverifikasi (Keycloak / tenant Microsoft milik sendiri)
+    pub trust_email: bool,
+    pub auth_url: Option<String>,
+    pub token_url: Option<String>,
+    pub userinfo_url: Option<String>,
+    /// Daftar email ala GitHub (`[{ email, primary, verified }]`), dipakai untuk mencari email utama yang terverifikasi
+    pub emails_url: Option<String>,
+}
+
+/// Bagian discovery document yang dipakai
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEndpointResponse {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    email: Option<String>,
+    /// Kebanyakan provider boolean, sebagian mengirim string `"true"`
+    email_verified: Option<serde_json::Value>,
+    name: Option<String>,
+    picture: Option<String>,
+    nonce: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderEmail {
+    email: String,
+    #[serde(default)]
+    primary: bool,
+    #[serde(default)]
+    verified: bool,
+}
+
+/// Algoritma ID token yang diterima, HMAC ditolak karena key-nya client secret
+const ID_TOKEN_ALGORITHMS: [Algorithm; 7] = [
+    Algorithm::RS256, Algorithm::RS384, Algorithm::RS512,
+    Algorithm::PS256, Algorithm::ES256, Algorithm::ES384, Algorithm::EdDSA,
+];
+
+static PROVIDERS: OnceCell<Vec<OidcProvider>> = OnceCell::new();
+
+/// 🌐 Registry provider OpenID Connect (Google, Microsoft, Keycloak, ...) + OAuth2 biasa (GitHub).
+///
+/// Provider aktif didaftarkan di `OIDC_PROVIDERS` (dipisah koma). Discovery document dan JWKS di-cache di Redis
+/// (`oidc:discovery:<name>`, `oidc:jwks:<name>`) selama `OIDC_CACHE_SECONDS` (default 3600), JWKS diambil ulang kalau `kid` tidak dikenal.
+/// `GOOGLE_ID` / `GOOGLE_SECRET` lama tetap dipakai untuk provider `google`. Registry dibaca sekali saat startup ([`OidcService::init`]).
+pub struct OidcService;
+
+impl OidcService {
+
+    fn cache_ttl() -> u64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get("OIDC_CACHE_SECONDS").and_then(|v| v.parse().ok()).unwrap_or(3600)
+    }
+
+    fn http_client() -> Result<oauth2::reqwest::Client, String> {
+        oauth2::reqwest::ClientBuilder::new()
+            .redirect(oauth2::reqwest::redirect::Policy::none())
+            .user_agent("snakesystem-api") // GitHub API menolak request tanpa User-Agent
+            .timeout(Duration::from_secs(10))
+            .build()
+            .map_err(|e| format!("Failed to build http client: {}", e))
+    }
+
+    /// Dipanggil sekali dari `main` setelah `SECRETS` siap, provider yang konfigurasinya salah dilaporkan sekali di sini
+    pub fn init() {
+        let providers = Self::providers();
+        println!("OIDC providers: {}", providers.iter().map(|provider| provider.name.as_str()).collect::<Vec<_>>().join(", "));
+    }
+
+    /// Semua provider yang konfigurasinya lengkap
+    pub fn providers() -> &'static [OidcProvider] {
+        PROVIDERS.get_or_init(Self::load_all)
+    }
+
+    fn load_all() -> Vec<OidcProvider> {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+
+        let mut names: Vec<String> = secrets.get("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| !name.is_empty())
+            .collect();
+
+        if secrets.get("GOOGLE_ID").is_some() && !names.iter().any(|name| name == "google") {
+            names.push("google".to_string());
+        }
+
+        names.iter().filter_map(|name| Self::load(name)).collect()
+    }
+
+    pub fn provider(name: &str) -> Option<OidcProvider> {
+        Self::providers().iter().find(|provider| provider.name == name).cloned()
+    }
+
+    fn load(name: &str) -> Option<OidcProvider> {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+        let get = |key: &str| secrets.get(&format!("{}{}", prefix, key)).filter(|value| !value.trim().is_empty());
+        let google = name == "google";
+
+        let client_id = get("CLIENT_ID").or_else(|| google.then(|| secrets.get("GOOGLE_ID")).flatten())?;
+        let client_secret = get("CLIENT_SECRET").or_else(|| google.then(|| secrets.get("GOOGLE_SECRET")).flatten()).unwrap_or_default();
+        let issuer = get("ISSUER")
+            .or_else(|| google.then(|| "https://accounts.google.com".to_string()))
+            .map(|issuer| issuer.trim_end_matches('/').to_string());
+
+        let (auth_url, token_url, userinfo_url) = (get("AUTH_URL"), get("TOKEN_URL"), get("USERINFO_URL"));
+
+        if issuer.is_none() && (auth_url.is_none() || token_url.is_none() || userinfo_url.is_none()) {
+            println!("❌ OIDC provider {} needs {}ISSUER or AUTH_URL + TOKEN_URL + USERINFO_URL", name, prefix);
+            return None;
+        }
+
+        let domain = secrets.get("DOMAIN").expect("secret was not found");
+        // Redirect URI Google lama sudah terdaftar di console, jadi dipertahankan
+        let redirect_url = get("REDIRECT_URL").unwrap_or_else(|| match google {
+            true => format!("{}/api/v1/auth/google/callback", domain),
+            false => format!("{}/api/v1/auth/oidc/{}/callback", domain, name),
+        });
+
+        let default_scopes = if issuer.is_some() { "openid email profile" } else { "" };
+
+        Some(OidcProvider {
+            name: name.to_string(),
+            display_name: get("NAME").unwrap_or_else(|| name.to_string()),
+            client_id,
+            client_secret,
+            scopes: get("SCOPES").unwrap_or_else(|| default_scopes.to_string()).split_whitespace().map(str::to_string).collect(),
+            redirect_url,
+            trust_email: get("TRUST_EMAIL").is_some_and(|value| value == "true"),
+            emails_url: get("EMAILS_URL"),
+            issuer,
+            auth_url,
+            token_url,
+            userinfo_url,
+        })
+    }
+
+    pub fn provider_infos() -> Vec<OidcProviderInfo> {
+        Self::providers().iter().map(|provider| OidcProviderInfo {
+            login_url: format!("/api/v1/auth/oidc/{}/login", provider.name),
+            name: provider.name.clone(),
+            display_name: provider.display_name.clone(),
+        }).collect()
+    }
+
+    fn cache_get(key: &str) -> Option<String> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        conn.get::<_, Option<String>>(key).unwrap_or_else(|e| {
+            println!("❌ OIDC Cache Error: {}", e);
+            None
+        })
+    }
+
+    fn cache_set(key: &str, value: &str) {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        if let Err(e) = conn.set_ex::<_, _, ()>(key, value, Self::cache_ttl()) {
+            println!("❌ OIDC Cache Error: {}", e);
+        }
+    }
+
+    async fn fetch_text(url: &str) -> Result<String, String> {
+        Self::http_client()?
+            .get(url)
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?
+            .text()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e))
+    }
+
+    /// Endpoint provider: dari discovery document (OIDC) atau dari konfigurasi manual (OAuth2 biasa)
+    pub async fn metadata(provider: &OidcProvider) -> Result<ProviderMetadata, String> {
+        let Some(issuer) = &provider.issuer else {
+            return Ok(ProviderMetadata {
+                issuer: String::new(),
+                authorization_endpoint: provider.auth_url.clone().unwrap_or_default(),
+                token_endpoint: provider.token_url.clone().unwrap_or_default(),
+                jwks_uri: None,
+                userinfo_endpoint: provider.userinfo_url.clone(),
+            });
+        };
+
+        let cache_key = format!("oidc:discovery:{}", provider.name);
+
+        let document = match Self::cache_get(&cache_key) {
+            Some(document) => document,
+            None => {
+                let document = Self::fetch_text(&format!("{}/.well-known/openid-configuration", issuer)).await?;
+                Self::cache_set(&cache_key, &document);
+                document
+            }
+        };
+
+        let mut metadata: ProviderMetadata = serde_json::from_str(&document)
+            .map_err(|e| format!("Invalid discovery document of {}: {}", provider.name, e))?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(format!("Discovery issuer mismatch for {}: {}", provider.name, metadata.issuer));
+        }
+
+        // Endpoint yang di-set manual menang (mis. token endpoint internal di balik proxy)
+        if let Some(auth_url) = &provider.auth_url { metadata.authorization_endpoint = auth_url.clone(); }
+        if let Some(token_url) = &provider.token_url { metadata.token_endpoint = token_url.clone(); }
+        if let Some(userinfo_url) = &provider.userinfo_url { metadata.userinfo_endpoint = Some(userinfo_url.clone()); }
+
+        Ok(metadata)
+    }
+
+    /// Buat URL consent (PKCE + state + nonce) dan simpan state di Redis. `Ok(None)` kalau IP kebanyakan login yang pending.
+    pub async fn authorization_url(provider: &OidcProvider, client_ip: String) -> Result<Option<String>, String> {
+        let metadata = Self::metadata(provider).await?;
+
+        let client = BasicClient::new(ClientId::new(provider.client_id.clone()))
+            .set_auth_uri(AuthUrl::new(metadata.authorization_endpoint).map_err(|e| format!("Invalid authorization endpoint: {}", e))?)
+            .set_redirect_uri(RedirectUrl::new(provider.redirect_url.clone()).map_err(|e| format!("Invalid redirect url: {}", e))?);
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = GenericService::random_string(32);
+
+        let (auth_url, csrf_token) = client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(provider.scopes.iter().cloned().map(oauth2::Scope::new))
+            .add_extra_param("nonce", &nonce)
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        let pending = PendingAuthorization {
+            pkce_verifier: pkce_verifier.secret().to_string(),
+            client_ip,
+            provider: provider.name.clone(),
+            nonce,
+        };
+
+        match OAuthStateStore::put(csrf_token.secret(), &pending) {
+            Ok(true) => Ok(Some(auth_url.to_string())),
+            Ok(false) => Ok(None),
+            Err(e) => Err(format!("Failed to store oauth state: {}", e)),
+        }
+    }
+
+    /// Tukar authorization code, validasi ID token (OIDC) atau ambil userinfo (OAuth2), lalu normalisasi profil user
+    pub async fn fetch_identity(provider: &OidcProvider, code: &str, pending: &PendingAuthorization) -> Result<ExternalIdentity, String> {
+        let metadata = Self::metadata(provider).await?;
+        let http_client = Self::http_client()?;
+
+        let body = http_client
+            .post(&metadata.token_endpoint)
+            .header("Accept", "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", provider.redirect_url.as_str()),
+                ("client_id", provider.client_id.as_str()),
+                ("client_secret", provider.client_secret.as_str()),
+                ("code_verifier", pending.pkce_verifier.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Token request failed: {}", e))?
+            .text()
+            .await
+            .map_err(|e| format!("Token request failed: {}", e))?;
+
+        // GitHub menjawab 200 + `{"error": ...}`, jadi cukup cek isi body
+        let tokens: TokenEndpointResponse = serde_json::from_str(&body)
+            .map_err(|_| format!("Failed to exchange authorization code: {}", body))?;
+
+        let mut identity = ExternalIdentity {
+            provider: provider.name.clone(),
+            subject: String::new(),
+            email: String::new(),
+            email_verified: false,
+            name: None,
+            picture: None,
+        };
+
+        if provider.issuer.is_some() {
+            let id_token = tokens.id_token.ok_or("Provider did not return an ID token")?;
+            let claims = Self::validate_id_token(provider, &metadata, &id_token, &pending.nonce).await?;
+
+            identity.subject = claims.sub;
+            identity.email = claims.email.unwrap_or_default();
+            identity.email_verified = claims.email_verified.is_some_and(|value| value == true || value == "true");
+            identity.name = claims.name;
+            identity.picture = claims.picture;
+        }
+
+        // OAuth2 biasa selalu lewat userinfo, OIDC hanya kalau ID token tidak membawa email
+        if identity.email.is_empty() {
+            if let Some(userinfo_url) = &metadata.userinfo_endpoint {
+                let info = Self::fetch_json(&http_client, userinfo_url, &tokens.access_token).await?;
+                let text = |key: &str| info[key].as_str().map(str::to_string).filter(|value| !value.is_empty());
+
+                let subject = text("sub").or_else(|| info["id"].as_i64().map(|id| id.to_string())).or_else(|| text("id")).unwrap_or_default();
+
+                // Userinfo OIDC wajib milik subject yang sama dengan ID token
+                if !identity.subject.is_empty() && subject != identity.subject {
+                    return Err("Userinfo subject does not match ID token".to_string());
+                }
+
+                identity.subject = subject;
+                identity.email = text("email").unwrap_or_default();
+                identity.email_verified = info["email_verified"].as_bool().or(info["verified_email"].as_bool()).unwrap_or(false);
+                identity.name = identity.name.or_else(|| text("name")).or_else(|| text("login"));
+                identity.picture = identity.picture.or_else(|| text("picture")).or_else(|| text("avatar_url"));
+            }
+        }
+
+        if let Some(emails_url) = &provider.emails_url {
+            let emails: Vec<ProviderEmail> = serde_json::from_value(Self::fetch_json(&http_client, emails_url, &tokens.access_token).await?)
+                .map_err(|e| format!("Invalid email list response: {}", e))?;
+
+            if let Some(primary) = emails.into_iter().find(|email| email.primary && email.verified) {
+                identity.email = primary.email;
+                identity.email_verified = true;
+            }
+        }
+
+        if identity.subject.is_empty() {
+            return Err("Provider did not return a user id".to_string());
+        }
+
+        identity.email = identity.email.trim().to_lowercase();
+        identity.email_verified = identity.email_verified || (provider.trust_email && !identity.email.is_empty());
+
+        Ok(identity)
+    }
+
+    async fn fetch_json(http_client: &oauth2::reqwest::Client, url: &str, access_token: &str) -> Result<serde_json::Value, String> {
+        let body = http_client
+            .get(url)
+            .bearer_auth(access_token)
+            .header("Accept", "application/json")
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?
+            .text()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e))?;
+
+        serde_json::from_str(&body).map_err(|e| format!("Invalid response from {}: {}", url, e))
+    }
+
+    async fn jwks(provider: &OidcProvider, metadata: &ProviderMetadata, refresh: bool) -> Result<JwkSet, String> {
+        let jwks_uri = metadata.jwks_uri.as_deref().ok_or("Discovery document has no jwks_uri")?;
+        let cache_key = format!("oidc:jwks:{}", provider.name);
+
+        let document = match (refresh, Self::cache_get(&cache_key)) {
+            (false, Some(document)) => document,
+            _ => {
+                let document = Self::fetch_text(jwks_uri).await?;
+                Self::cache_set(&cache_key, &document);
+                document
+            }
+        };
+
+        serde_json::from_str(&document).map_err(|e| format!("Invalid JWKS of {}: {}", provider.name, e))
+    }
+
+    /// 🔏 Validasi ID token: signature (JWKS provider), `iss`, `aud`, `exp`, dan `nonce`
+    async fn validate_id_token(provider: &OidcProvider, metadata: &ProviderMetadata, id_token: &str, nonce: &str) -> Result<IdTokenClaims, String> {
+        if let Some(claims) = Self::check_id_token(provider, metadata, &Self::jwks(provider, metadata, false).await?, id_token, nonce)? {
+            return Ok(claims);
+        }
+
+        // `kid` baru = provider baru rotasi key, ambil ulang JWKS sekali
+        Self::check_id_token(provider, metadata, &Self::jwks(provider, metadata, true).await?, id_token, nonce)?
+            .ok_or_else(|| "Unknown ID token signing key".to_string())
+    }
+
+    /// Validasi ID token dengan JWKS yang sudah ada, `Ok(None)` kalau key penandatangannya tidak ada di `jwks`
+    fn check_id_token(provider: &OidcProvider, metadata: &ProviderMetadata, jwks: &JwkSet, id_token: &str, nonce: &str) -> Result<Option<IdTokenClaims>, String> {
+        let header = decode_header(id_token).map_err(|e| format!("Invalid ID token: {}", e))?;
+
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(format!("Unsupported ID token algorithm: {:?}", header.alg));
+        }
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        };
+
+        let Some(jwk) = jwk else { return Ok(None) };
+
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| format!("Invalid JWK: {}", e))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[metadata.issuer.as_str()]);
+        validation.set_audience(&[provider.client_id.as_str()]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| format!("Invalid ID token: {}", e))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) || nonce.is_empty() {
+            return Err("Invalid ID token nonce".to_string());
+        }
+
+        Ok(Some(claims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use ring::{rand::SystemRandom, signature::{Ed25519KeyPair, KeyPair}};
+
+    use super::*;
+
+    const ISSUER: &str = "https://id.example.com";
+    const CLIENT_ID: &str = "snakesystem-web";
+
+    struct SigningKey {
+        kid: String,
+        encoding_key: EncodingKey,
+        jwk: serde_json::Value,
+    }
+
+    fn signing_key(kid: &str) -> SigningKey {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+
+        SigningKey {
+            kid: kid.to_string(),
+            encoding_key: EncodingKey::from_ed_der(pkcs8.as_ref()),
+            jwk: serde_json::json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": kid,
+                "x": URL_SAFE_NO_PAD.encode(pair.public_key().as_ref()),
+            }),
+        }
+    }
+
+    fn jwks(keys: &[&SigningKey]) -> JwkSet {
+        serde_json::from_value(serde_json::json!({ "keys": keys.iter().map(|key| key.jwk.clone()).collect::<Vec<_>>() })).unwrap()
+    }
+
+    fn provider() -> OidcProvider {
+        OidcProvider {
+            name: "keycloak".to_string(),
+            display_name: "Keycloak".to_string(),
+            issuer: Some(ISSUER.to_string()),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: "client-secret".to_string(),
+            scopes: vec!["openid".to_string()],
+            redirect_url: "https://api.example.com/api/v1/auth/oidc/keycloak/callback".to_string(),
+            trust_email: false,
+            auth_url: None,
+            token_url: None,
+            userinfo_url: None,
+            emails_url: None,
+        }
+    }
+
+    fn metadata() -> ProviderMetadata {
+        ProviderMetadata {
+            issuer: ISSUER.to_string(),
+            authorization_endpoint: format!("{}/auth", ISSUER),
+            token_endpoint: format!("{}/token", ISSUER),
+            jwks_uri: Some(format!("{}/certs", ISSUER)),
+            userinfo_endpoint: None,
+        }
+    }
+
+    fn claims() -> serde_json::Value {
+        serde_json::json!({
+            "iss": ISSUER,
+            "aud": CLIENT_ID,
+            "sub": "user-1",
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "email": "user@example.com",
+            "email_verified": true,
+            "nonce": "nonce-1",
+        })
+    }
+
+    fn sign(key: &SigningKey, claims: &serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(key.kid.clone());
+        encode(&header, claims, &key.encoding_key).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_id_token() {
+        let key = signing_key("key-1");
+        let other = signing_key("key-2");
+        let token = sign(&key, &claims());
+
+        let claims = OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&other, &key]), &token, "nonce-1")
+            .unwrap()
+            .expect("signing key should be found");
+
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.email.as_deref(), Some("user@example.com"));
+    }
+
+    #[test]
+    fn rejects_wrong_issuer_or_audience() {
+        let key = signing_key("key-1");
+
+        let mut wrong_issuer = claims();
+        wrong_issuer["iss"] = "https://evil.example.com".into();
+        assert!(OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &sign(&key, &wrong_issuer), "nonce-1").is_err());
+
+        let mut wrong_audience = claims();
+        wrong_audience["aud"] = "another-client".into();
+        assert!(OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &sign(&key, &wrong_audience), "nonce-1").is_err());
+    }
+
+    #[test]
+    fn rejects_expired_id_token() {
+        let key = signing_key("key-1");
+        let mut expired = claims();
+        expired["exp"] = (chrono::Utc::now().timestamp() - 3600).into();
+
+        assert!(OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &sign(&key, &expired), "nonce-1").is_err());
+    }
+
+    #[test]
+    fn rejects_nonce_mismatch() {
+        let key = signing_key("key-1");
+        let token = sign(&key, &claims());
+
+        assert_eq!(
+            OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &token, "nonce-2").err(),
+            Some("Invalid ID token nonce".to_string())
+        );
+
+        let mut without_nonce = claims();
+        without_nonce.as_object_mut().unwrap().remove("nonce");
+        assert!(OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &sign(&key, &without_nonce), "").is_err());
+    }
+
+    #[test]
+    fn rejects_hs256_id_token() {
+        let key = signing_key("key-1");
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(key.kid.clone());
+        // HMAC dengan client secret, yang juga diketahui pihak lain yang pegang konfigurasi client
+        let token = encode(&header, &claims(), &EncodingKey::from_secret(provider().client_secret.as_bytes())).unwrap();
+
+        assert_eq!(
+            OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &token, "nonce-1").err(),
+            Some("Unsupported ID token algorithm: HS256".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_kid_needs_jwks_refresh() {
+        let key = signing_key("key-1");
+        let rotated = signing_key("key-2");
+        let token = sign(&rotated, &claims());
+
+        assert!(OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &token, "nonce-1").unwrap().is_none());
+
+        // kid sama tapi key beda (JWKS palsu / key diganti) tetap gagal signature
+        let impostor = signing_key("key-1");
+        assert!(OidcService::check_id_token(&provider(), &metadata(), &jwks(&[&key]), &sign(&impostor, &claims()), "nonce-1").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use actix_web::HttpRequest;
+use rand::{rng, Rng};
+use redis::Commands;
+use sqlx::Row;
+
+use crate::{middleware::{crypto::hash_token, password::constant_time_eq, jwt_session::Claims, model::ActionResult}, services::{audit_service::{AuditService, SecurityEvent, SecurityEventType}, auth_service::AuthService, mail_service::MailService}, CONNECTION, REDIS_CLIENT, SECRETS};
+
+/// Hitung percobaan sebelum kode dibandingkan, supaya tebakan paralel tetap kena batas.
+/// KEYS: kode, counter percobaan. ARGV: maksimal percobaan, TTL counter.
+/// Return `{0, '', 0}` kode tidak ada, `{1, '', n}` sudah melewati batas (kode dihapus), `{2, hash, n}` boleh dibandingkan.
+const ATTEMPT_SCRIPT: &str = r#"
+local stored = redis.call('GET', KEYS[1])
+if not stored then return {0, '', 0} end
+
+local attempts = redis.call('INCR', KEYS[2])
+if attempts == 1 then redis.call('EXPIRE', KEYS[2], ARGV[2]) end
+
+if attempts > tonumber(ARGV[1]) then
+  redis.call('DEL', KEYS[1], KEYS[2])
+  return {1, '', attempts}
+end
+
+return {2, stored, attempts}
+"#;
+
+/// Hasil pengecekan kode OTP / konfirmasi
+#[derive(Debug, PartialEq)]
+enum CodeCheck {
+    Valid,
+    /// Kode salah, tidak ada, atau sudah dipakai request lain
+    Invalid,
+    /// Batas percobaan habis, kode sudah dihapus
+    TooManyAttempts,
+}
+
+/// 📧 Login tanpa password dengan kode OTP yang dikirim ke email.
+///
+/// Kode disimpan di Redis dalam bentuk SHA-256 (`otp:login:<email>`), berlaku `OTP_TTL_SECONDS` (default 300),
+/// maksimal `OTP_MAX_ATTEMPTS` (default 5) kali dicoba (dihitung atomik sebelum dibandingkan), dan hanya bisa diminta ulang setelah `OTP_RESEND_SECONDS` (default 60).
+pub struct OtpService;
+
+impl OtpService {
+
+    fn setting(key: &str, default: u64) -> u64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    fn normalize(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+
+    /// Reservasi satu percobaan lewat [`ATTEMPT_SCRIPT`], lalu bandingkan. Kode yang cocok diambil dengan `GETDEL`,
+    /// jadi hanya satu request yang bisa memakainya
+    fn check_code(code_key: &str, attempts_key: &str, code: &str) -> Result<CodeCheck, redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let max_attempts = Self::setting("OTP_MAX_ATTEMPTS", 5);
+
+        let (status, stored, attempts): (u8, String, u64) = redis::Script::new(ATTEMPT_SCRIPT)
+            .key(code_key)
+            .key(attempts_key)
+            .arg(max_attempts)
+            .arg(Self::setting("OTP_TTL_SECONDS", 300))
+            .invoke(&mut conn)?;
+
+        match status {
+            0 => return Ok(CodeCheck::Invalid),
+            1 => return Ok(CodeCheck::TooManyAttempts),
+            _ => {}
+        }
+
+        if !constant_time_eq(stored.as_bytes(), hash_token(code.trim()).as_bytes()) {
+            if attempts >= max_attempts {
+                conn.del::<_, ()>(&[code_key, attempts_key])?;
+                return Ok(CodeCheck::TooManyAttempts);
+            }
+            return Ok(CodeCheck::Invalid);
+        }
+
+        // Kode sekali pakai, request paralel dengan kode yang sama hanya satu yang menang
+        let consumed: Option<String> = redis::cmd("GETDEL").arg(code_key).query(&mut conn)?;
+        conn.del::<_, ()>(attempts_key)?;
+
+        Ok(match consumed {
+            Some(consumed) if consumed == stored => CodeCheck::Valid,
+            _ => CodeCheck::Invalid,
+        })
+    }
+
+    pub async fn request(email: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let email = Self::normalize(email);
+
+        let ttl = Self::setting("OTP_TTL_SECONDS", 300);
+        let cooldown = Self::setting("OTP_RESEND_SECONDS", 60);
+
+        // Jawaban selalu sama supaya endpoint tidak bisa dipakai untuk cek email terdaftar
+        result.result = true;
+        result.message = "If the email is registered, a login code has been sent".to_string();
+
+        // Cooldown per email, SET NX gagal berarti kode baru saja dikirim
+        let allowed: bool = match redis::cmd("SET")
+            .arg(format!("otp:login:cooldown:{}", email))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(cooldown)
+            .query::<Option<String>>(&mut conn) {
+                Ok(reply) => reply.is_some(),
+                Err(e) => {
+                    result.result = false;
+                    result.error = Some(format!("Redis error: {}", e));
+                    return result;
+                }
+            };
+
+        if !allowed {
+            return result;
+        }
+
+        let user = match sqlx::query(r#"SELECT B.fullname FROM users A
+                LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+                WHERE lower(A.email) = $1 AND A.disable_login = false"#)
+            .bind(&email)
+            .fetch_optional(connection)
+            .await {
+                Ok(row) => row,
+                Err(e) => {
+                    result.result = false;
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            };
+
+        let Some(user) = user else { return result };
+
+        let code = format!("{:06}", rng().random_range(0..1_000_000));
+
+        if let Err(e) = redis::pipe()
+            .atomic()
+            .set_ex(format!("otp:login:{}", email), hash_token(&code), ttl).ignore()
+            .del(format!("otp:login:attempts:{}", email)).ignore()
+            .query::<()>(&mut conn) {
+                result.result = false;
+                result.error = Some(format!("Redis error: {}", e));
+                return result;
+            }
+
+        let mut mail_data = HashMap::new();
+        mail_data.insert("username".to_string(), user.try_get::<Option<String>, _>("fullname").unwrap_or_default());
+        mail_data.insert("company_name".to_string(), Some("PT. TECH SNAKE SYSTEM".to_string()));
+        mail_data.insert("subject".to_string(), Some("Kode Login Anda".to_string()));
+        mail_data.insert("email".to_string(), Some(email.clone()));
+        mail_data.insert("title".to_string(), Some("LOGIN OTP CUSTOMER ONBOARDING".to_string()));
+        mail_data.insert("otp_code".to_string(), Some(code));
+        mail_data.insert("expires_minutes".to_string(), Some((ttl / 60).max(1).to_string()));
+
+        let mail_result: ActionResult<String, String> = MailService::send(mail_data, "otp-login").await;
+
+        if let Some(e) = mail_result.error {
+            println!("❌ Mail Error: {}", e);
+        }
+
+        result
+    }
+
+    /// Kirim kode konfirmasi (`otp:confirm:<usernid>`) untuk hapus akun / ganti email,
+    /// hanya untuk akun tanpa password (dibuat lewat login provider) yang tidak bisa konfirmasi dengan password
+    pub async fn request_confirmation(usernid: i32) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        let ttl = Self::setting("OTP_TTL_SECONDS", 300);
+        let cooldown = Self::setting("OTP_RESEND_SECONDS", 60);
+
+        let user = match sqlx::query(r#"SELECT A.email, COALESCE(A.password, '') <> '' AS has_password, B.fullname FROM users A
+                LEFT JOIN user_kyc B ON A.web_cif_id = B.autonid
+                WHERE A.web_cif_id = $1"#)
+            .bind(usernid)
+            .fetch_optional(connection)
+            .await {
+                Ok(Some(row)) => row,
+                Ok(None) => {
+                    result.message = "User not found".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            };
+
+        if user.try_get::<bool, _>("has_password").unwrap_or(true) {
+            result.message = "Use your current password to confirm".to_string();
+            return result;
+        }
+
+        let allowed: bool = match redis::cmd("SET")
+            .arg(format!("otp:confirm:cooldown:{}", usernid))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(cooldown)
+            .query::<Option<String>>(&mut conn) {
+                Ok(reply) => reply.is_some(),
+                Err(e) => {
+                    result.error = Some(format!("Redis error: {}", e));
+                    return result;
+                }
+            };
+
+        if !allowed {
+            result.message = "A confirmation code was sent recently, please wait before requesting another".to_string();
+            return result;
+        }
+
+        let code = format!("{:06}", rng().random_range(0..1_000_000));
+
+        if let Err(e) = redis::pipe()
+            .atomic()
+            .set_ex(format!("otp:confirm:{}", usernid), hash_token(&code), ttl).ignore()
+            .del(format!("otp:confirm:attempts:{}", usernid)).ignore()
+            .query::<()>(&mut conn) {
+                result.error = Some(format!("Redis error: {}", e));
+                return result;
+            }
+
+        let mut mail_data = HashMap::new();
+        mail_data.insert("username".to_string(), user.try_get::<Option<String>, _>("fullname").unwrap_or_default());
+        mail_data.insert("company_name".to_string(), Some("PT. TECH SNAKE SYSTEM".to_string()));
+        mail_data.insert("subject".to_string(), Some("Kode Konfirmasi Akun".to_string()));
+        mail_data.insert("email".to_string(), Some(user.get::<String, _>("email")));
+        mail_data.insert("title".to_string(), Some("KEAMANAN AKUN CUSTOMER ONBOARDING".to_string()));
+        mail_data.insert("action".to_string(), Some("hapus akun / ganti email".to_string()));
+        mail_data.insert("otp_code".to_string(), Some(code));
+        mail_data.insert("expires_minutes".to_string(), Some((ttl / 60).max(1).to_string()));
+
+        let mail_result: ActionResult<String, String> = MailService::send(mail_data, "account-confirm-code").await;
+
+        if let Some(e) = mail_result.error {
+            println!("❌ Mail Error: {}", e);
+        }
+
+        result.result = true;
+        result.message = "Confirmation code has been sent to your email".to_string();
+        result
+    }
+
+    /// Cek kode konfirmasi (sekali pakai, maksimal `OTP_MAX_ATTEMPTS` kali dicoba)
+    pub fn verify_confirmation(usernid: i32, code: &str) -> Result<bool, redis::RedisError> {
+        let check = Self::check_code(
+            &format!("otp:confirm:{}", usernid),
+            &format!("otp:confirm:attempts:{}", usernid),
+            code,
+        )?;
+
+        Ok(check == CodeCheck::Valid)
+    }
+
+    /// Tukar kode dengan user, hasil gagal dicatat di `security_events` seperti login password
+    pub async fn verify(email: &str, code: &str, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let result = Self::otp_login(email, code, req, app_name).await;
+
+        if result.data.is_none() {
+            AuditService::record(SecurityEvent {
+                email: Some(Self::normalize(email)),
+                success: false,
+                detail: Some(format!("otp: {}", result.error.clone().unwrap_or_else(|| result.message.clone()))),
+                ..SecurityEvent::from_request(SecurityEventType::LoginFailed, req)
+            }).await;
+        }
+
+        result
+    }
+
+    async fn otp_login(email: &str, code: &str, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let mut result: ActionResult<Claims, String> = ActionResult::default();
+        let email = Self::normalize(email);
+
+        match Self::check_code(&format!("otp:login:{}", email), &format!("otp:login:attempts:{}", email), code) {
+            Ok(CodeCheck::Valid) => {}
+            Ok(CodeCheck::Invalid) => {
+                result.message = "Invalid or expired code".to_string();
+                return result;
+            }
+            Ok(CodeCheck::TooManyAttempts) => {
+                result.message = "Too many invalid codes, please request a new one".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(format!("Redis error: {}", e));
+                return result;
+            }
+        }
+
+        AuthService::login_by_email(&email, req, app_name).await
+    }
+}
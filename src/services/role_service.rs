@@ -0,0 +1,155 @@
+use sqlx::Row;
+
+use crate::{middleware::{jwt_session::Claims, model::{ActionResult, RoleInfo}}, services::generic_service::GenericService, CONNECTION};
+
+pub struct RoleService;
+
+impl RoleService {
+
+    /// Isi `roles` + `permissions` di claims dari tabel `user_roles` / `role_permissions`
+    pub async fn attach_access(claims: &mut Claims) -> Result<(), String> {
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        let rows = sqlx::query(r#"SELECT r.name AS role_name, p.name AS permission_name
+            FROM user_roles ur
+            JOIN roles r ON r.role_id = ur.role_id
+            LEFT JOIN role_permissions rp ON rp.role_id = r.role_id
+            LEFT JOIN permissions p ON p.permission_id = rp.permission_id
+            WHERE ur.user_nid = $1"#)
+            .bind(claims.usernid)
+            .fetch_all(connection)
+            .await
+            .map_err(|e| format!("Failed to fetch roles: {}", e))?;
+
+        let mut roles: Vec<String> = Vec::new();
+        let mut permissions: Vec<String> = Vec::new();
+
+        for row in rows {
+            let role: String = row.get("role_name");
+            if !roles.contains(&role) {
+                roles.push(role);
+            }
+            if let Some(permission) = row.get::<Option<String>, _>("permission_name") {
+                if !permissions.contains(&permission) {
+                    permissions.push(permission);
+                }
+            }
+        }
+
+        claims.roles = roles;
+        claims.permissions = permissions;
+        Ok(())
+    }
+
+    pub async fn list_roles() -> ActionResult<Vec<RoleInfo>, String> {
+        let mut result: ActionResult<Vec<RoleInfo>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"SELECT r.name, r.description,
+                COALESCE(array_agg(p.name ORDER BY p.name) FILTER (WHERE p.name IS NOT NULL), '{}') AS permissions
+            FROM roles r
+            LEFT JOIN role_permissions rp ON rp.role_id = r.role_id
+            LEFT JOIN permissions p ON p.permission_id = rp.permission_id
+            GROUP BY r.role_id, r.name, r.description
+            ORDER BY r.name"#)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => {
+                    result.result = true;
+                    result.data = Some(rows.iter().map(|row| RoleInfo {
+                        name: row.get("name"),
+                        description: row.get("description"),
+                        permissions: row.get("permissions"),
+                    }).collect());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch roles: {}", e));
+                }
+            }
+
+        result
+    }
+
+    pub async fn user_roles(usernid: i32) -> ActionResult<Vec<String>, String> {
+        let mut result: ActionResult<Vec<String>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"SELECT r.name FROM user_roles ur
+            JOIN roles r ON r.role_id = ur.role_id
+            WHERE ur.user_nid = $1
+            ORDER BY r.name"#)
+            .bind(usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => {
+                    result.result = true;
+                    result.data = Some(rows.iter().map(|row| row.get("name")).collect());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch roles: {}", e));
+                }
+            }
+
+        result
+    }
+
+    pub async fn assign_role(usernid: i32, role: &str, assigned_by: i32) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"INSERT INTO user_roles (user_nid, role_id, assigned_by, assigned_at)
+            SELECT u.web_cif_id, r.role_id, $3, $4
+            FROM users u, roles r
+            WHERE u.web_cif_id = $1 AND r.name = $2
+            ON CONFLICT (user_nid, role_id) DO NOTHING"#)
+            .bind(usernid)
+            .bind(role)
+            .bind(assigned_by)
+            .bind(GenericService::get_timestamp())
+            .execute(connection)
+            .await {
+                Ok(_) => {
+                    // Tidak ada baris baru bisa berarti sudah punya role, cek ulang supaya pesannya jelas
+                    match Self::user_roles(usernid).await.data {
+                        Some(roles) if roles.iter().any(|r| r == role) => {
+                            result.result = true;
+                            result.message = format!("Role {} assigned", role);
+                        }
+                        _ => {
+                            result.message = "User or role not found".to_string();
+                        }
+                    }
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to insert user_roles: {}", e));
+                }
+            }
+
+        result
+    }
+
+    pub async fn revoke_role(usernid: i32, role: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"DELETE FROM user_roles
+            WHERE user_nid = $1 AND role_id = (SELECT role_id FROM roles WHERE name = $2)"#)
+            .bind(usernid)
+            .bind(role)
+            .execute(connection)
+            .await {
+                Ok(row) if row.rows_affected() > 0 => {
+                    result.result = true;
+                    result.message = format!("Role {} revoked", role);
+                }
+                Ok(_) => {
+                    result.message = "User does not have this role".to_string();
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to delete user_roles: {}", e));
+                }
+            }
+
+        result
+    }
+}
@@ -0,0 +1,61 @@
+use redis::Commands;
+
+use crate::{middleware::jwt_session::{access_token_ttl, validate_jwt, Claims}, REDIS_CLIENT};
+
+/// 🚫 Denylist access token di Redis.
+///
+/// - `jwt:deny:<jti>`: token tertentu dicabut (logout / revoke session), TTL sampai `exp` token
+/// - `jwt:revoked-before:<usernid>`: semua token user dengan `iat` sebelum nilai ini (unix timestamp) dicabut,
+///   TTL selama umur access token karena token yang lebih tua sudah expired dengan sendirinya
+pub struct TokenRevocationService;
+
+impl TokenRevocationService {
+
+    /// Cabut satu token sampai token itu expired
+    pub fn revoke(claims: &Claims) -> Result<(), redis::RedisError> {
+        if claims.jti.is_empty() {
+            return Ok(());
+        }
+
+        let ttl = (claims.exp as i64 - chrono::Utc::now().timestamp()).max(1) as u64;
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let _: () = conn.set_ex(format!("jwt:deny:{}", claims.jti), 1, ttl)?;
+        Ok(())
+    }
+
+    /// Cabut access token yang tersimpan di tabel `cookies` (nilai `token_cookie`), token rusak / expired diabaikan
+    pub fn revoke_token(token: &str) -> Result<(), redis::RedisError> {
+        match validate_jwt(token) {
+            Ok(claims) => Self::revoke(&claims),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Cabut semua token user yang dibuat sebelum `before` (unix timestamp)
+    pub fn revoke_user_before(usernid: i32, before: i64) -> Result<(), redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        let key = format!("jwt:revoked-before:{}", usernid);
+
+        // Jangan mundurkan batas yang sudah ada
+        let current: Option<i64> = conn.get(&key)?;
+        let before = current.map_or(before, |current| current.max(before));
+
+        let ttl = access_token_ttl().num_seconds().max(1) as u64 + 60;
+        let _: () = conn.set_ex(&key, before, ttl)?;
+        Ok(())
+    }
+
+    pub fn is_revoked(claims: &Claims) -> Result<bool, redis::RedisError> {
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        if !claims.jti.is_empty() {
+            let denied: bool = conn.exists(format!("jwt:deny:{}", claims.jti))?;
+            if denied {
+                return Ok(true);
+            }
+        }
+
+        let revoked_before: Option<i64> = conn.get(format!("jwt:revoked-before:{}", claims.usernid))?;
+        Ok(revoked_before.is_some_and(|before| (claims.iat as i64) < before))
+    }
+}
@@ -1,6 +1,6 @@
 use sqlx::PgPool;
 
-use crate::{middleware::{jwt_session::Claims, model::ActionResult}, services::{data_service::DataService}, CONNECTION};
+use crate::{middleware::{jwt_session::Claims, model::ActionResult}, services::{data_service::DataService, generic_service::GenericService, kyc_service::KycService}, CONNECTION};
 
 pub struct UserService;
 
@@ -124,7 +124,18 @@ impl UserService {
             Ok(row) => {
 
                 result.result = true;
-                let json_obj = DataService::row_to_json(&row);
+                let mut json_obj = DataService::row_to_json(&row);
+                if KycService::open_fields(session.usernid, &mut json_obj) {
+                    if let Err(e) = KycService::seal_user(session.usernid).await {
+                        eprintln!("❌ KYC encryption error: {}", e);
+                    }
+                }
+
+                // Admin yang impersonate tidak perlu melihat nomor identitas / rekening lengkap
+                if session.impersonator.is_some() {
+                    KycService::mask_fields(&mut json_obj);
+                }
+
                 result.data = Some(json_obj);
             }
             Err(e) => {
@@ -135,4 +146,151 @@ impl UserService {
 
         return result;
     }
-}
\ No newline at end of file
+
+    /// 📦 Semua data yang kita simpan tentang user (akun, KYC, request, session, role, akun login eksternal, passkey, riwayat keamanan) dalam satu arsip JSON.
+    /// Kolom rahasia (password, secret 2FA, token / key) tidak ikut diekspor.
+    pub async fn export_data(session: Claims) -> ActionResult<serde_json::Value, String> {
+        let mut result: ActionResult<serde_json::Value, String> = ActionResult::default();
+
+        let connection: &PgPool = CONNECTION.get().unwrap();
+
+        let account = match sqlx::query(r#"SELECT web_cif_id, email, handphone, picture, google_id, client_category,
+                register_date, activate_time, disable_login, totp_enabled, totp_enabled_at,
+                pending_email, deletion_scheduled_at
+            FROM users
+            WHERE web_cif_id = $1"#)
+            .bind(session.usernid)
+            .fetch_optional(connection)
+            .await {
+                Ok(Some(row)) => DataService::row_to_json(&row),
+                Ok(None) => {
+                    result.message = "User not found".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch users: {}", e));
+                    return result;
+                }
+            };
+
+        let mut kyc = match sqlx::query(r#"SELECT * FROM user_kyc WHERE autonid = $1"#)
+            .bind(session.usernid)
+            .fetch_optional(connection)
+            .await {
+                Ok(row) => row.map(|row| DataService::row_to_json(&row)),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch user_kyc: {}", e));
+                    return result;
+                }
+            };
+
+        if let Some(kyc) = kyc.as_mut() {
+            if KycService::open_fields(session.usernid, kyc) {
+                if let Err(e) = KycService::seal_user(session.usernid).await {
+                    eprintln!("❌ KYC encryption error: {}", e);
+                }
+            }
+
+            // Sama seperti profil, arsip yang diunduh admin saat impersonate tidak berisi nomor identitas / rekening lengkap
+            if session.impersonator.is_some() {
+                KycService::mask_fields(kyc);
+            }
+        }
+
+        let requests = match sqlx::query(r#"SELECT * FROM user_request WHERE web_cif_nid = $1"#)
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows.iter().map(DataService::row_to_json).collect::<Vec<_>>(),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch user_request: {}", e));
+                    return result;
+                }
+            };
+
+        let sessions = match sqlx::query(r#"SELECT session_id, app_name, app_computer_name, app_ip_address, app_device, created_at, last_update
+            FROM cookies
+            WHERE user_nid = $1
+            ORDER BY last_update DESC"#)
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows.iter().map(DataService::row_to_json).collect::<Vec<_>>(),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch cookies: {}", e));
+                    return result;
+                }
+            };
+
+        let roles = match sqlx::query(r#"SELECT R.name AS role, UR.assigned_at
+            FROM user_roles UR
+            JOIN roles R ON R.role_id = UR.role_id
+            WHERE UR.user_nid = $1"#)
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows.iter().map(DataService::row_to_json).collect::<Vec<_>>(),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch user_roles: {}", e));
+                    return result;
+                }
+            };
+
+        let identities = match sqlx::query(r#"SELECT provider, subject, email, created_at, last_login_at
+            FROM user_identities
+            WHERE user_nid = $1"#)
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows.iter().map(DataService::row_to_json).collect::<Vec<_>>(),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch user_identities: {}", e));
+                    return result;
+                }
+            };
+
+        let security_events = match sqlx::query(r#"SELECT event_type, success, ip_address, device_name, user_agent, detail, created_at
+            FROM security_events
+            WHERE user_nid = $1
+            ORDER BY created_at DESC"#)
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows.iter().map(DataService::row_to_json).collect::<Vec<_>>(),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch security_events: {}", e));
+                    return result;
+                }
+            };
+
+        // Public key tidak ikut, cukup identitas passkey dan kapan dipakai
+        let passkeys = match sqlx::query(r#"SELECT credential_id, name, transports, created_at, last_used_at
+            FROM webauthn_credentials
+            WHERE user_nid = $1
+            ORDER BY created_at"#)
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows.iter().map(DataService::row_to_json).collect::<Vec<_>>(),
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch webauthn_credentials: {}", e));
+                    return result;
+                }
+            };
+
+        result.result = true;
+        result.data = Some(serde_json::json!({
+            "exported_at": GenericService::get_timestamp().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "account": account,
+            "kyc": kyc,
+            "requests": requests,
+            "sessions": sessions,
+            "roles": roles,
+            "identities": identities,
+            "passkeys": passkeys,
+            "security_events": security_events,
+        }));
+
+        result
+    }
+}
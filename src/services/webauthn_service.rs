@@ -0,0 +1,505 @@
+use actix_web::HttpRequest;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::RngCore;
+use redis::Commands;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+
+use crate::{middleware::{jwt_session::Claims, model::{ActionResult, PasskeyCredential, PasskeyInfo, PasskeyLoginRequest, PasskeyRegisterRequest}, webauthn::{cose_algorithm, decode_cbor, verify_signature, AuthenticatorData, Cbor, SUPPORTED_ALGORITHMS}}, services::{audit_service::{AuditService, SecurityEvent, SecurityEventType}, auth_service::AuthService, generic_service::GenericService}, CONNECTION, REDIS_CLIENT, SECRETS};
+
+/// 🔑 Login dengan passkey (WebAuthn).
+///
+/// Relying party ID diambil dari host secret `DOMAIN`, origin yang diterima sama dengan CORS (`CORS_ALLOWED_ORIGINS`, default `FRONT_URL`).
+/// Challenge disimpan di Redis (`webauthn:reg:<challenge>` / `webauthn:auth:<challenge>`) selama `WEBAUTHN_CHALLENGE_SECONDS` (default 300)
+/// dan hanya bisa dipakai sekali. Attestation tidak diverifikasi (`attestation: "none"`).
+pub struct WebauthnService;
+
+/// `clientDataJSON` yang sudah di-decode
+struct ClientData {
+    raw: Vec<u8>,
+    challenge: String,
+}
+
+impl WebauthnService {
+
+    fn challenge_ttl() -> u64 {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get("WEBAUTHN_CHALLENGE_SECONDS").and_then(|v| v.parse().ok()).unwrap_or(300)
+    }
+
+    /// Host dari `DOMAIN` (tanpa skema, port, path)
+    fn rp_id() -> String {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        let domain = secrets.get("DOMAIN").expect("secret was not found");
+        let host = domain.split("://").last().unwrap_or_default();
+        host.split(['/', ':']).next().unwrap_or_default().to_lowercase()
+    }
+
+    fn allowed_origins() -> Vec<String> {
+        let secrets = SECRETS.get().expect("SECRETS not initialized");
+        secrets.get("CORS_ALLOWED_ORIGINS")
+            .or_else(|| secrets.get("FRONT_URL"))
+            .unwrap_or_default()
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect()
+    }
+
+    fn decode(value: &str) -> Result<Vec<u8>, String> {
+        URL_SAFE_NO_PAD.decode(value.trim().trim_end_matches('=')).map_err(|_| "Invalid base64url value".to_string())
+    }
+
+    /// User handle = usernid 4 byte big-endian
+    fn user_handle(usernid: i32) -> String {
+        URL_SAFE_NO_PAD.encode(usernid.to_be_bytes())
+    }
+
+    fn new_challenge() -> String {
+        let mut bytes = [0u8; 32];
+        rand::rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Cek `type`, origin, lalu kembalikan challenge untuk dicocokkan dengan Redis
+    fn parse_client_data(credential: &PasskeyCredential, expected_type: &str) -> Result<ClientData, String> {
+        if credential.credential_type != "public-key" {
+            return Err("Invalid credential type".to_string());
+        }
+
+        let raw = Self::decode(&credential.response.client_data_json)?;
+        let client_data: serde_json::Value = serde_json::from_slice(&raw).map_err(|_| "Invalid clientDataJSON".to_string())?;
+
+        if client_data["type"].as_str() != Some(expected_type) {
+            return Err("Invalid client data type".to_string());
+        }
+
+        let origin = client_data["origin"].as_str().unwrap_or_default().trim_end_matches('/');
+        if !Self::allowed_origins().iter().any(|allowed| allowed == origin) {
+            return Err(format!("Origin not allowed: {}", origin));
+        }
+
+        let challenge = client_data["challenge"].as_str().unwrap_or_default().to_string();
+        Ok(ClientData { raw, challenge })
+    }
+
+    /// Ambil sekaligus hapus challenge, `None` kalau tidak ada / sudah dipakai / expired
+    fn consume_challenge(prefix: &str, challenge: &str) -> Result<Option<String>, redis::RedisError> {
+        if challenge.is_empty() {
+            return Ok(None);
+        }
+
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+        conn.get_del(format!("webauthn:{}:{}", prefix, challenge))
+    }
+
+    pub async fn registration_options(session: &Claims) -> ActionResult<serde_json::Value, String> {
+        let mut result: ActionResult<serde_json::Value, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        let existing = match sqlx::query("SELECT credential_id, transports FROM webauthn_credentials WHERE user_nid = $1")
+            .bind(session.usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch webauthn_credentials: {}", e));
+                    return result;
+                }
+            };
+
+        let ttl = Self::challenge_ttl();
+        let challenge = Self::new_challenge();
+
+        if let Err(e) = conn.set_ex::<_, _, ()>(format!("webauthn:reg:{}", challenge), session.usernid, ttl) {
+            result.error = Some(format!("Redis error: {}", e));
+            return result;
+        }
+
+        let exclude: Vec<serde_json::Value> = existing.iter().map(|row| serde_json::json!({
+            "type": "public-key",
+            "id": row.get::<String, _>("credential_id"),
+            "transports": row.get::<Vec<String>, _>("transports"),
+        })).collect();
+
+        let params: Vec<serde_json::Value> = SUPPORTED_ALGORITHMS.iter()
+            .map(|alg| serde_json::json!({ "type": "public-key", "alg": alg }))
+            .collect();
+
+        result.result = true;
+        result.data = Some(serde_json::json!({
+            "challenge": challenge,
+            "rp": { "id": Self::rp_id(), "name": "Snakesystem" },
+            "user": {
+                "id": Self::user_handle(session.usernid),
+                "name": session.email,
+                "displayName": session.fullname,
+            },
+            "pubKeyCredParams": params,
+            "timeout": ttl * 1000,
+            "attestation": "none",
+            "authenticatorSelection": { "residentKey": "preferred", "userVerification": "preferred" },
+            "excludeCredentials": exclude,
+        }));
+
+        result
+    }
+
+    pub async fn finish_registration(session: &Claims, request: PasskeyRegisterRequest) -> ActionResult<PasskeyInfo, String> {
+        let mut result: ActionResult<PasskeyInfo, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let credential = &request.credential;
+
+        let client_data = match Self::parse_client_data(credential, "webauthn.create") {
+            Ok(client_data) => client_data,
+            Err(e) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        match Self::consume_challenge("reg", &client_data.challenge) {
+            Ok(Some(owner)) if owner == session.usernid.to_string() => {}
+            Ok(_) => {
+                result.message = "Invalid or expired challenge".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(format!("Redis error: {}", e));
+                return result;
+            }
+        }
+
+        let parsed = Self::decode(credential.response.attestation_object.as_deref().unwrap_or_default())
+            .and_then(|bytes| decode_cbor(&bytes))
+            .and_then(|(attestation, _)| match attestation.get_text("authData").and_then(Cbor::as_bytes) {
+                Some(auth_data) => AuthenticatorData::parse(auth_data),
+                None => Err("Attestation object has no authData".to_string()),
+            });
+
+        let auth_data = match parsed {
+            Ok(auth_data) => auth_data,
+            Err(e) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        if !auth_data.matches_rp_id(&Self::rp_id()) {
+            result.message = "Relying party ID mismatch".to_string();
+            return result;
+        }
+
+        if !auth_data.user_present() {
+            result.message = "User presence is required".to_string();
+            return result;
+        }
+
+        let (Some(credential_id), Some(public_key)) = (auth_data.credential_id, auth_data.public_key) else {
+            result.message = "Attested credential data is missing".to_string();
+            return result;
+        };
+
+        let credential_id = URL_SAFE_NO_PAD.encode(credential_id);
+        if credential_id != credential.id.trim_end_matches('=') {
+            result.message = "Credential ID mismatch".to_string();
+            return result;
+        }
+
+        let algorithm = match cose_algorithm(&public_key) {
+            Ok(algorithm) => algorithm as i32,
+            Err(e) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        let name = request.name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+
+        let inserted = sqlx::query(r#"INSERT INTO webauthn_credentials
+                (credential_id, user_nid, name, public_key, algorithm, sign_count, transports, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (credential_id) DO NOTHING
+                RETURNING to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at"#)
+            .bind(&credential_id)
+            .bind(session.usernid)
+            .bind(&name)
+            .bind(URL_SAFE_NO_PAD.encode(&public_key))
+            .bind(algorithm)
+            .bind(auth_data.sign_count as i64)
+            .bind(&credential.response.transports)
+            .bind(GenericService::get_timestamp())
+            .fetch_optional(connection)
+            .await;
+
+        match inserted {
+            Ok(Some(row)) => {
+                AuditService::record(SecurityEvent {
+                    detail: Some(credential_id.clone()),
+                    ..SecurityEvent::from_claims(SecurityEventType::PasskeyRegistered, session)
+                }).await;
+
+                result.result = true;
+                result.message = "Passkey registered".to_string();
+                result.data = Some(PasskeyInfo {
+                    credential_id,
+                    name,
+                    algorithm,
+                    transports: credential.response.transports.clone(),
+                    created_at: row.get("created_at"),
+                    last_used_at: None,
+                });
+            }
+            Ok(None) => {
+                result.message = "Passkey already registered".to_string();
+            }
+            Err(e) => {
+                result.error = Some(format!("Failed to insert webauthn_credentials: {}", e));
+            }
+        }
+
+        result
+    }
+
+    /// Tanpa email: login discoverable (browser menampilkan passkey yang tersimpan).
+    /// Dengan email: `allowCredentials` berisi passkey milik email itu.
+    pub async fn authentication_options(email: Option<String>) -> ActionResult<serde_json::Value, String> {
+        let mut result: ActionResult<serde_json::Value, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let mut conn = REDIS_CLIENT.get().expect("Redis not initialized").clone();
+
+        let mut allow: Vec<serde_json::Value> = Vec::new();
+
+        if let Some(email) = email.map(|email| email.trim().to_lowercase()).filter(|email| !email.is_empty()) {
+            match sqlx::query(r#"SELECT C.credential_id, C.transports FROM webauthn_credentials C
+                    JOIN users A ON A.web_cif_id = C.user_nid
+                    WHERE lower(A.email) = $1"#)
+                .bind(&email)
+                .fetch_all(connection)
+                .await {
+                    Ok(rows) => {
+                        allow = rows.iter().map(|row| serde_json::json!({
+                            "type": "public-key",
+                            "id": row.get::<String, _>("credential_id"),
+                            "transports": row.get::<Vec<String>, _>("transports"),
+                        })).collect();
+                    }
+                    Err(e) => {
+                        result.error = Some(format!("Failed to fetch webauthn_credentials: {}", e));
+                        return result;
+                    }
+                }
+        }
+
+        let ttl = Self::challenge_ttl();
+        let challenge = Self::new_challenge();
+
+        if let Err(e) = conn.set_ex::<_, _, ()>(format!("webauthn:auth:{}", challenge), 1, ttl) {
+            result.error = Some(format!("Redis error: {}", e));
+            return result;
+        }
+
+        result.result = true;
+        result.data = Some(serde_json::json!({
+            "challenge": challenge,
+            "rpId": Self::rp_id(),
+            "timeout": ttl * 1000,
+            "userVerification": "preferred",
+            "allowCredentials": allow,
+        }));
+
+        result
+    }
+
+    /// Verifikasi assertion, hasil gagal dicatat di `security_events` seperti login password
+    pub async fn finish_authentication(request: PasskeyLoginRequest, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let result = Self::passkey_login(request, req, app_name).await;
+
+        if result.data.is_none() {
+            AuditService::record(SecurityEvent {
+                success: false,
+                detail: Some(format!("passkey: {}", result.error.clone().unwrap_or_else(|| result.message.clone()))),
+                ..SecurityEvent::from_request(SecurityEventType::LoginFailed, req)
+            }).await;
+        }
+
+        result
+    }
+
+    async fn passkey_login(request: PasskeyLoginRequest, req: &HttpRequest, app_name: &str) -> ActionResult<Claims, String> {
+        let mut result: ActionResult<Claims, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+        let credential = &request.credential;
+
+        let client_data = match Self::parse_client_data(credential, "webauthn.get") {
+            Ok(client_data) => client_data,
+            Err(e) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        match Self::consume_challenge("auth", &client_data.challenge) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                result.message = "Invalid or expired challenge".to_string();
+                return result;
+            }
+            Err(e) => {
+                result.error = Some(format!("Redis error: {}", e));
+                return result;
+            }
+        }
+
+        let row = match sqlx::query(r#"SELECT C.user_nid, C.public_key, C.sign_count, A.email
+                FROM webauthn_credentials C
+                JOIN users A ON A.web_cif_id = C.user_nid
+                WHERE C.credential_id = $1"#)
+            .bind(credential.id.trim_end_matches('='))
+            .fetch_optional(connection)
+            .await {
+                Ok(Some(row)) => row,
+                Ok(None) => {
+                    result.message = "Unknown passkey".to_string();
+                    return result;
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch webauthn_credentials: {}", e));
+                    return result;
+                }
+            };
+
+        let user_nid: i32 = row.get("user_nid");
+        let stored_count: i64 = row.get("sign_count");
+        let email: String = row.get("email");
+
+        if let Some(user_handle) = credential.response.user_handle.as_deref().filter(|handle| !handle.is_empty()) {
+            if user_handle.trim_end_matches('=') != Self::user_handle(user_nid) {
+                result.message = "User handle mismatch".to_string();
+                return result;
+            }
+        }
+
+        let raw_auth_data = match Self::decode(credential.response.authenticator_data.as_deref().unwrap_or_default()) {
+            Ok(raw) => raw,
+            Err(e) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        let auth_data = match AuthenticatorData::parse(&raw_auth_data) {
+            Ok(auth_data) => auth_data,
+            Err(e) => {
+                result.message = e;
+                return result;
+            }
+        };
+
+        if !auth_data.matches_rp_id(&Self::rp_id()) {
+            result.message = "Relying party ID mismatch".to_string();
+            return result;
+        }
+
+        if !auth_data.user_present() {
+            result.message = "User presence is required".to_string();
+            return result;
+        }
+
+        let verified = Self::decode(&row.get::<String, _>("public_key"))
+            .and_then(|public_key| {
+                let signature = Self::decode(credential.response.signature.as_deref().unwrap_or_default())?;
+                let mut message = raw_auth_data.clone();
+                message.extend_from_slice(&Sha256::digest(&client_data.raw));
+                verify_signature(&public_key, &message, &signature)
+            });
+
+        if let Err(e) = verified {
+            result.message = e;
+            return result;
+        }
+
+        // Authenticator tanpa counter selalu kirim 0, selain itu counter wajib naik (indikasi passkey di-clone)
+        let new_count = auth_data.sign_count as i64;
+        if (new_count != 0 || stored_count != 0) && new_count <= stored_count {
+            result.message = "Passkey sign counter did not increase".to_string();
+            return result;
+        }
+
+        // Cek ulang di UPDATE supaya dua assertion paralel dengan counter yang sama tidak sama-sama lolos
+        match sqlx::query(r#"UPDATE webauthn_credentials SET sign_count = $1, last_used_at = $2
+                WHERE credential_id = $3 AND (sign_count < $1 OR ($1 = 0 AND sign_count = 0))"#)
+            .bind(new_count)
+            .bind(GenericService::get_timestamp())
+            .bind(credential.id.trim_end_matches('='))
+            .execute(connection)
+            .await {
+                Ok(done) if done.rows_affected() == 0 => {
+                    result.message = "Passkey sign counter did not increase".to_string();
+                    return result;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    result.error = Some(format!("Failed to update webauthn_credentials: {}", e));
+                    return result;
+                }
+            }
+
+        AuthService::login_by_email(&email, req, app_name).await
+    }
+
+    pub async fn list_credentials(usernid: i32) -> ActionResult<Vec<PasskeyInfo>, String> {
+        let mut result: ActionResult<Vec<PasskeyInfo>, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query(r#"SELECT credential_id, name, algorithm, transports,
+                to_char(created_at, 'YYYY-MM-DD HH24:MI:SS') AS created_at,
+                to_char(last_used_at, 'YYYY-MM-DD HH24:MI:SS') AS last_used_at
+            FROM webauthn_credentials WHERE user_nid = $1 ORDER BY created_at DESC"#)
+            .bind(usernid)
+            .fetch_all(connection)
+            .await {
+                Ok(rows) => {
+                    result.result = true;
+                    result.data = Some(rows.iter().map(|row| PasskeyInfo {
+                        credential_id: row.get("credential_id"),
+                        name: row.get("name"),
+                        algorithm: row.get("algorithm"),
+                        transports: row.get("transports"),
+                        created_at: row.get("created_at"),
+                        last_used_at: row.get("last_used_at"),
+                    }).collect());
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to fetch webauthn_credentials: {}", e));
+                }
+            }
+
+        result
+    }
+
+    pub async fn delete_credential(usernid: i32, credential_id: &str) -> ActionResult<String, String> {
+        let mut result: ActionResult<String, String> = ActionResult::default();
+        let connection = CONNECTION.get().expect("DB_POOL not initialized");
+
+        match sqlx::query("DELETE FROM webauthn_credentials WHERE user_nid = $1 AND credential_id = $2")
+            .bind(usernid)
+            .bind(credential_id)
+            .execute(connection)
+            .await {
+                Ok(done) if done.rows_affected() > 0 => {
+                    result.result = true;
+                    result.message = "Passkey deleted".to_string();
+                }
+                Ok(_) => {
+                    result.message = "Passkey not found".to_string();
+                }
+                Err(e) => {
+                    result.error = Some(format!("Failed to delete webauthn_credentials: {}", e));
+                }
+            }
+
+        result
+    }
+}